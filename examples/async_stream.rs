@@ -0,0 +1,26 @@
+//! Counts the accounts in a snapshot streamed over HTTP, without blocking the runtime.
+//!
+//! ```shell
+//! cargo run --example async_stream --features async -- https://example.com/snapshot.tar.zst
+//! ```
+
+use futures_util::StreamExt;
+use solana_snapshot_etl::append_vec_iter;
+use solana_snapshot_etl::async_archived::{AsyncArchiveSnapshotExtractor, AsyncSnapshotExtractor};
+use solana_snapshot_etl::loader::HttpOptions;
+use std::rc::Rc;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = std::env::args().nth(1).ok_or("usage: async_stream <URL>")?;
+    let mut extractor =
+        AsyncArchiveSnapshotExtractor::download(&url, &HttpOptions::default()).await?;
+
+    let mut accounts = 0usize;
+    let mut append_vecs = extractor.stream();
+    while let Some(append_vec) = append_vecs.next().await {
+        accounts += append_vec_iter(Rc::new(append_vec?)).count();
+    }
+    println!("{} accounts", accounts);
+    Ok(())
+}
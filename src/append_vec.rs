@@ -16,6 +16,7 @@
 // Source: solana/runtime/src/append_vec.rs
 
 use {
+    crate::OwnedAccount,
     log::*,
     memmap2::{Mmap, MmapMut},
     serde::{Deserialize, Serialize},
@@ -24,6 +25,7 @@ use {
         clock::Epoch,
         hash::Hash,
         pubkey::Pubkey,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH,
     },
     std::{
         convert::TryFrom,
@@ -31,6 +33,8 @@ use {
         io::{self, Read},
         mem,
         path::Path,
+        ptr,
+        sync::Arc,
     },
 };
 
@@ -45,6 +49,9 @@ macro_rules! u64_align {
 
 pub const MAXIMUM_APPEND_VEC_FILE_SIZE: u64 = 16 * 1024 * 1024 * 1024; // 16 GiB
 
+/// Default for [`AppendVec::set_max_data_len`], the largest account Solana permits (10 MiB).
+pub const DEFAULT_MAX_DATA_LEN: u64 = MAX_PERMITTED_DATA_LENGTH;
+
 pub type StoredMetaWriteVersion = u64;
 
 /// Meta contains enough context to recover the index from storage itself
@@ -119,19 +126,49 @@ impl<'a> StoredAccountMeta<'a> {
     }
 }
 
+impl<'a> ReadableAccount for StoredAccountMeta<'a> {
+    fn lamports(&self) -> u64 {
+        self.account_meta.lamports
+    }
+    fn data(&self) -> &[u8] {
+        self.data
+    }
+    fn owner(&self) -> &Pubkey {
+        &self.account_meta.owner
+    }
+    fn executable(&self) -> bool {
+        self.account_meta.executable
+    }
+    fn rent_epoch(&self) -> Epoch {
+        self.account_meta.rent_epoch
+    }
+}
+
 /// A thread-safe, file-backed block of memory used to store `Account` instances. Append operations
 /// are serialized such that only one thread updates the internal `append_lock` at a time. No
 /// restrictions are placed on reading. That is, one may read items from one thread while another
 /// is appending new items.
+///
+/// Clones share the same memory map.
+#[derive(Clone)]
 pub struct AppendVec {
     /// A file-backed block of memory that is used to store the data for each appended item.
-    map: Mmap,
+    map: Arc<Mmap>,
 
     /// The number of bytes used to store items, not the number of items.
     current_len: usize,
 
     /// The number of bytes available for storing items.
     file_size: u64,
+
+    /// The slot this AppendVec was written in.
+    slot: u64,
+
+    /// The ID of this AppendVec, unique within its slot.
+    id: u64,
+
+    /// Accounts declaring more data than this are treated as corrupt.
+    max_data_len: u64,
 }
 
 impl AppendVec {
@@ -176,7 +213,32 @@ impl AppendVec {
         self.file_size
     }
 
-    pub fn new_from_file<P: AsRef<Path>>(path: P, current_len: usize) -> io::Result<Self> {
+    /// The raw bytes of the stored accounts, `len()` bytes long.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map[..self.current_len]
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sets the largest `data_len` an account may declare, [`DEFAULT_MAX_DATA_LEN`] by default.
+    ///
+    /// Reading stops at an account declaring more, as its size can't be trusted.
+    pub fn set_max_data_len(&mut self, max_data_len: u64) {
+        self.max_data_len = max_data_len;
+    }
+
+    pub fn new_from_file<P: AsRef<Path>>(
+        path: P,
+        current_len: usize,
+        slot: u64,
+        id: u64,
+    ) -> io::Result<Self> {
         let data = OpenOptions::new()
             .read(true)
             .write(false)
@@ -196,21 +258,111 @@ impl AppendVec {
         };
 
         let new = AppendVec {
-            map,
+            map: Arc::new(map),
             current_len,
             file_size,
+            slot,
+            id,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
         };
 
         Ok(new)
     }
 
-    pub fn new_from_reader<R: Read>(reader: &mut R, current_len: usize) -> io::Result<Self> {
+    pub fn new_from_reader<R: Read>(
+        reader: &mut R,
+        current_len: usize,
+        slot: u64,
+        id: u64,
+    ) -> io::Result<Self> {
         let mut map = MmapMut::map_anon(current_len)?;
         io::copy(&mut reader.take(current_len as u64), &mut map.as_mut())?;
         Ok(AppendVec {
-            map: map.make_read_only()?,
+            map: Arc::new(map.make_read_only()?),
+            current_len,
+            file_size: current_len as u64,
+            slot,
+            id,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
+        })
+    }
+
+    /// Lays out `accounts` in a new in-memory AppendVec, e.g. to pass accounts read from
+    /// another format to code expecting AppendVecs.
+    ///
+    /// Account hashes are not known and left zeroed.
+    pub fn new_from_accounts(accounts: &[OwnedAccount], slot: u64, id: u64) -> io::Result<Self> {
+        let current_len: usize = accounts
+            .iter()
+            .map(|account| Self::stored_size(account.data.len()))
+            .sum();
+        Self::sanitize_len_and_size(current_len, current_len)?;
+        let mut map = MmapMut::map_anon(current_len)?;
+        let mut offset = 0;
+        for account in accounts {
+            let account_meta_offset = u64_align!(offset + mem::size_of::<StoredMeta>());
+            let hash_offset = u64_align!(account_meta_offset + mem::size_of::<AccountMeta>());
+            let data_offset = u64_align!(hash_offset + mem::size_of::<Hash>());
+            //UNSAFE: The offsets are aligned and within the map, which is page aligned.
+            //Fields are written one by one so that padding keeps the map's zeroes.
+            unsafe {
+                let meta = map.as_mut_ptr().add(offset) as *mut StoredMeta;
+                ptr::addr_of_mut!((*meta).write_version).write(account.write_version);
+                ptr::addr_of_mut!((*meta).pubkey).write(account.pubkey);
+                ptr::addr_of_mut!((*meta).data_len).write(account.data.len() as u64);
+                let account_meta = map.as_mut_ptr().add(account_meta_offset) as *mut AccountMeta;
+                ptr::addr_of_mut!((*account_meta).lamports).write(account.lamports);
+                ptr::addr_of_mut!((*account_meta).owner).write(account.owner);
+                ptr::addr_of_mut!((*account_meta).executable).write(account.executable);
+                ptr::addr_of_mut!((*account_meta).rent_epoch).write(account.rent_epoch);
+            }
+            map[data_offset..data_offset + account.data.len()].copy_from_slice(&account.data);
+            offset += Self::stored_size(account.data.len());
+        }
+        Ok(AppendVec {
+            map: Arc::new(map.make_read_only()?),
             current_len,
             file_size: current_len as u64,
+            slot,
+            id,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
+        })
+    }
+
+    /// Size of an account with `data_len` bytes of data, including its metadata and padding.
+    pub fn stored_size(data_len: usize) -> usize {
+        let header = u64_align!(mem::size_of::<StoredMeta>())
+            + u64_align!(mem::size_of::<AccountMeta>())
+            + u64_align!(mem::size_of::<Hash>());
+        u64_align!(header + data_len)
+    }
+
+    /// Like [`Self::new_from_reader`], but reads from an async stream.
+    #[cfg(feature = "async")]
+    pub async fn new_from_async_reader<R: tokio::io::AsyncRead + Unpin>(
+        reader: &mut R,
+        current_len: usize,
+        slot: u64,
+        id: u64,
+    ) -> io::Result<Self> {
+        use tokio::io::AsyncReadExt;
+
+        let mut map = MmapMut::map_anon(current_len)?;
+        let mut filled = 0;
+        while filled < current_len {
+            let n = reader.read(&mut map[filled..]).await?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        Ok(AppendVec {
+            map: Arc::new(map.make_read_only()?),
+            current_len,
+            file_size: current_len as u64,
+            slot,
+            id,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
         })
     }
 
@@ -252,6 +404,13 @@ impl AppendVec {
         let (meta, next): (&'a StoredMeta, _) = self.get_type(offset)?;
         let (account_meta, next): (&'a AccountMeta, _) = self.get_type(next)?;
         let (hash, next): (&'a Hash, _) = self.get_type(next)?;
+        if meta.data_len > self.max_data_len {
+            warn!(
+                "Account at offset {} of AppendVec {}.{} declares {} bytes of data, more than the maximum of {}, skipping the rest of the AppendVec",
+                offset, self.slot, self.id, meta.data_len, self.max_data_len
+            );
+            return None;
+        }
         let (data, next) = self.get_slice(next, meta.data_len as usize)?;
         let stored_size = next - offset;
         Some((
@@ -1,24 +1,83 @@
+use crate::append_vec::DEFAULT_MAX_DATA_LEN;
+use crate::compression::Compression;
+use crate::seekable::{ParallelSeekableDecoder, SeekableDecoder};
+use crate::status_cache::{is_status_cache_file, read_status_cache, StatusCacheEntry};
+use crate::sysvars::SnapshotSysvars;
 use crate::{
-    deserialize_from, parse_append_vec_name, AccountsDbFields, AppendVec, AppendVecIterator,
-    DeserializableVersionedBank, Result, SerializableAccountStorageEntry, SnapshotError,
-    SnapshotExtractor,
+    checked_append_vec_len, deserialize_from, parse_append_vec_name, parse_append_vec_path,
+    AccountsDbFields, AppendVec, AppendVecIterator, BankSummary, DeserializableVersionedBank,
+    Result, SerializableAccountStorageEntry, SnapshotError, SnapshotExtractor,
 };
-use log::info;
+use log::{info, warn};
+use solana_sdk::clock::Slot;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::{Component, Path};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
 use std::time::Instant;
 use tar::{Archive, Entries, Entry};
 
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Decompressed tar stream, seekable if the archive has a zstd seek table.
+enum TarStream {
+    Sequential(Box<dyn Read>),
+    Seekable(Box<dyn ReadSeek>),
+}
+
+impl Read for TarStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            TarStream::Sequential(rd) => rd.read(buf),
+            TarStream::Seekable(rd) => rd.read(buf),
+        }
+    }
+}
+
+impl Seek for TarStream {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            TarStream::Sequential(_) => Err(std::io::ErrorKind::Unsupported.into()),
+            TarStream::Seekable(rd) => rd.seek(pos),
+        }
+    }
+}
+
+/// A file in a snapshot archive.
+#[derive(Clone, Debug)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    /// Uncompressed size in bytes.
+    pub size: u64,
+}
+
 /// Extracts account data from a .tar.zst stream.
 pub struct ArchiveSnapshotExtractor<Source>
 where
     Source: Read + Unpin + 'static,
 {
     accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry>,
-    _archive: Pin<Box<Archive<zstd::Decoder<'static, BufReader<Source>>>>>,
-    entries: Option<Entries<'static, zstd::Decoder<'static, BufReader<Source>>>>,
+    bank: BankSummary,
+    slot_range: RangeInclusive<Slot>,
+    strict: bool,
+    max_data_len: u64,
+    /// Entries up to and including the manifest, which are read on open.
+    leading_entries: Vec<ArchiveEntry>,
+    /// Raw status cache, if it was stored before the manifest.
+    status_cache: Option<Vec<u8>>,
+    /// AppendVec read while looking for the status cache after the manifest.
+    pending_entry: Option<Entry<'static, TarStream>>,
+    stream_counters: StreamCounters,
+    _archive: Pin<Box<Archive<TarStream>>>,
+    entries: Option<Entries<'static, TarStream>>,
+    _source: PhantomData<Source>,
 }
 
 impl<Source> SnapshotExtractor for ArchiveSnapshotExtractor<Source>
@@ -35,22 +94,65 @@ where
     Source: Read + Unpin + 'static,
 {
     pub fn from_reader(source: Source) -> Result<Self> {
-        let tar_stream = zstd::stream::read::Decoder::new(source)?;
+        let counters = StreamCounters::default();
+        let tar_stream = zstd::stream::read::Decoder::new(counters.count_compressed(source))?;
+        Self::from_tar_stream(
+            TarStream::Sequential(Box::new(counters.count_decompressed(tar_stream))),
+            counters,
+        )
+    }
+
+    /// Like [`Self::from_reader`], but decompresses on a background thread.
+    ///
+    /// zstd frames can't be decoded in parallel, so this runs a single decoder
+    /// thread ahead of the tar reader and AppendVec consumers.
+    pub fn from_reader_threaded(source: Source) -> Result<Self>
+    where
+        Source: Send,
+    {
+        let counters = StreamCounters::default();
+        let tar_stream = zstd::stream::read::Decoder::new(counters.count_compressed(source))?;
+        Self::from_tar_stream(
+            TarStream::Sequential(Box::new(ReadAheadThread::spawn(
+                counters.count_decompressed(tar_stream),
+            ))),
+            counters,
+        )
+    }
+
+    fn from_tar_stream(tar_stream: TarStream, stream_counters: StreamCounters) -> Result<Self> {
+        let seekable = matches!(tar_stream, TarStream::Seekable(_));
         let mut archive = Box::pin(Archive::new(tar_stream));
 
         // This is safe as long as we guarantee that entries never gets accessed past drop.
         let archive_static = unsafe { &mut *((&mut *archive) as *mut Archive<_>) };
-        let mut entries = archive_static.entries()?;
+        // Seeking skips the data of entries that aren't read, e.g. AppendVecs outside the slot range.
+        let mut entries = if seekable {
+            archive_static.entries_with_seek()?
+        } else {
+            archive_static.entries()?
+        };
 
         // Search for snapshot manifest.
         let mut snapshot_file: Option<Entry<_>> = None;
+        let mut leading_entries = Vec::new();
+        let mut status_cache = None;
         for entry in entries.by_ref() {
-            let entry = entry?;
-            let path = entry.path()?;
-            if Self::is_snapshot_manifest_file(&path) {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            leading_entries.push(ArchiveEntry {
+                path: path.clone(),
+                size: entry.size(),
+            });
+            if is_status_cache_file(&path) {
+                // The stream can't be rewound, so keep it in case it is requested later.
+                let mut buf = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut buf)?;
+                status_cache = Some(buf);
+            } else if is_snapshot_manifest_file(&path) {
                 snapshot_file = Some(entry);
                 break;
-            } else if Self::is_appendvec_file(&path) {
+            } else if is_appendvec_file(&path) {
                 // TODO Support archives where AppendVecs precede snapshot manifests
                 return Err(SnapshotError::UnexpectedAppendVec);
             }
@@ -64,11 +166,13 @@ where
 
         let pre_unpack = Instant::now();
         let versioned_bank: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
+        let mut bank = BankSummary::from(&versioned_bank);
         drop(versioned_bank);
         let versioned_bank_post_time = Instant::now();
 
         let accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry> =
             deserialize_from(&mut snapshot_file)?;
+        bank.set_accounts_db_fields(&accounts_db_fields);
         let accounts_db_fields_post_time = Instant::now();
         drop(snapshot_file);
 
@@ -84,32 +188,137 @@ where
         Ok(ArchiveSnapshotExtractor {
             _archive: archive,
             accounts_db_fields,
+            bank,
+            slot_range: 0..=Slot::MAX,
+            strict: false,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
+            leading_entries,
+            status_cache,
+            pending_entry: None,
+            stream_counters,
             entries: Some(entries),
+            _source: PhantomData,
         })
     }
 
-    fn unboxed_iter(&mut self) -> impl Iterator<Item = Result<AppendVec>> + '_ {
-        self.entries
-            .take()
-            .into_iter()
-            .flatten()
-            .filter_map(|entry| {
-                let mut entry = match entry {
-                    Ok(x) => x,
-                    Err(e) => return Some(Err(e.into())),
-                };
-                let path = match entry.path() {
-                    Ok(x) => x,
-                    Err(e) => return Some(Err(e.into())),
-                };
-                let (slot, id) = path.file_name().and_then(parse_append_vec_name)?;
-                Some(self.process_entry(&mut entry, slot, id))
+    /// Bank fields from the snapshot manifest.
+    pub fn bank(&self) -> &BankSummary {
+        &self.bank
+    }
+
+    /// Epoch schedule, rent, and fee parameters from the snapshot manifest.
+    pub fn sysvars(&self) -> &SnapshotSysvars {
+        &self.bank.sysvars
+    }
+
+    /// Counts bytes read from the source and produced by the decoder.
+    ///
+    /// The handle can be polled while the extractor is iterated.
+    pub fn stream_counters(&self) -> StreamCounters {
+        self.stream_counters.clone()
+    }
+
+    /// Only yields AppendVecs whose slot is within the range.
+    pub fn set_slot_range(&mut self, slot_range: RangeInclusive<Slot>) {
+        self.slot_range = slot_range;
+    }
+
+    /// Fails on AppendVecs that are shorter than declared in the manifest, instead of warning.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets the largest `data_len` accepted from accounts, see [`AppendVec::set_max_data_len`].
+    pub fn set_max_data_len(&mut self, max_data_len: u64) {
+        self.max_data_len = max_data_len;
+    }
+
+    /// Reads the status cache.
+    ///
+    /// The status cache is stored next to the manifest, before the AppendVecs.
+    /// If it follows the manifest, this reads ahead in the stream,
+    /// so it must be called before [`SnapshotExtractor::iter`].
+    pub fn status_cache(&mut self) -> Result<Vec<StatusCacheEntry>> {
+        if let Some(status_cache) = self.status_cache.take() {
+            return read_status_cache(&status_cache[..]);
+        }
+        let entries = self.entries.as_mut().ok_or(SnapshotError::NoStatusCache)?;
+        for entry in entries.by_ref() {
+            let entry = entry?;
+            let path = entry.path()?.into_owned();
+            if is_status_cache_file(&path) {
+                return read_status_cache(BufReader::new(entry));
+            } else if is_appendvec_file(&path) {
+                // Hand the AppendVec to the next iter() call.
+                self.pending_entry = Some(entry);
+                break;
+            }
+        }
+        Err(SnapshotError::NoStatusCache)
+    }
+
+    /// Lists the files in the archive without decoding accounts, like `tar -t`.
+    ///
+    /// The archive is a stream, so this consumes the remaining entries:
+    /// [`SnapshotExtractor::iter`] yields nothing afterwards, and vice versa.
+    pub fn list_entries(&mut self) -> impl Iterator<Item = Result<ArchiveEntry>> + '_ {
+        let pending = self.pending_entry.take().map(Ok);
+        let entries = self.entries.take().into_iter().flatten();
+        let remaining = pending.into_iter().chain(entries).map(|entry| {
+            let entry = entry?;
+            Ok(ArchiveEntry {
+                path: entry.path()?.into_owned(),
+                size: entry.size(),
             })
+        });
+        std::mem::take(&mut self.leading_entries)
+            .into_iter()
+            .map(Ok)
+            .chain(remaining)
+    }
+
+    fn unboxed_iter(&mut self) -> impl Iterator<Item = Result<AppendVec>> + '_ {
+        std::iter::from_fn(move || self.next_append_vec())
+    }
+
+    /// Reads entries until the next AppendVec, `None` once the archive is exhausted.
+    fn next_append_vec(&mut self) -> Option<Result<AppendVec>> {
+        loop {
+            let entry = match self.pending_entry.take() {
+                Some(entry) => Ok(entry),
+                None => self.entries.as_mut()?.next()?,
+            };
+            if let Some(result) = self.read_entry(entry) {
+                return Some(result);
+            }
+        }
+    }
+
+    fn read_entry(
+        &self,
+        entry: std::io::Result<Entry<'static, TarStream>>,
+    ) -> Option<Result<AppendVec>> {
+        let mut entry = match entry {
+            Ok(x) => x,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let path = match entry.path() {
+            Ok(x) => x.into_owned(),
+            Err(e) => return recover_member_error(Path::new("?"), e.into(), self.strict),
+        };
+        let (slot, id) = parse_append_vec_path(&path)?;
+        if !self.slot_range.contains(&slot) {
+            return None;
+        }
+        match self.process_entry(&mut entry, slot, id) {
+            Ok(append_vec) => Some(Ok(append_vec)),
+            Err(e) => recover_member_error(&path, e, self.strict),
+        }
     }
 
     fn process_entry(
         &self,
-        entry: &mut Entry<'static, zstd::Decoder<'static, BufReader<Source>>>,
+        entry: &mut Entry<'static, TarStream>,
         slot: u64,
         id: u64,
     ) -> Result<AppendVec> {
@@ -124,51 +333,308 @@ where
             None => return Err(SnapshotError::UnexpectedAppendVec),
             Some(v) => v,
         };
-        Ok(AppendVec::new_from_reader(
-            entry,
+        let len = checked_append_vec_len(
+            slot,
+            id,
+            entry.size(),
             known_vec.accounts_current_len,
-        )?)
+            self.strict,
+        )?;
+        let mut append_vec = AppendVec::new_from_reader(entry, len, slot, id)?;
+        append_vec.set_max_data_len(self.max_data_len);
+        Ok(append_vec)
     }
+}
 
-    fn is_snapshot_manifest_file(path: &Path) -> bool {
-        let mut components = path.components();
-        if components.next() != Some(Component::Normal("snapshots".as_ref())) {
-            return false;
-        }
-        let slot_number_str_1 = match components.next() {
-            Some(Component::Normal(slot)) => slot,
-            _ => return false,
+impl<Source> IntoIterator for ArchiveSnapshotExtractor<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    type Item = Result<AppendVec>;
+    type IntoIter = IntoIter<Source>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter { extractor: self }
+    }
+}
+
+/// Owning iterator over the AppendVecs of an archive, see [`ArchiveSnapshotExtractor::into_iter`].
+pub struct IntoIter<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    extractor: ArchiveSnapshotExtractor<Source>,
+}
+
+impl<Source> Iterator for IntoIter<Source>
+where
+    Source: Read + Unpin + 'static,
+{
+    type Item = Result<AppendVec>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.extractor.next_append_vec()
+    }
+}
+
+impl ArchiveSnapshotExtractor<File> {
+    /// Opens a snapshot archive file.
+    ///
+    /// The codec is detected from the magic bytes, falling back to the file extension
+    /// (`.tar.zst`, `.tar.gz`, `.tar.bz2`, `.tar.xz`). Plain `.tar` files are read as is.
+    ///
+    /// zstd archives with a seek table ([`crate::seekable`]) skip over the data
+    /// of AppendVecs outside the slot range instead of decompressing it.
+    pub fn open(path: &Path) -> Result<Self> {
+        let counters = StreamCounters::default();
+        let tar_stream = match Self::open_seekable(path, &counters, 0)? {
+            Some(tar_stream) => tar_stream,
+            None => TarStream::Sequential(Self::open_tar_stream(path, &counters)?),
         };
-        // Check if slot number file is valid u64.
-        if slot_number_str_1
-            .to_str()
-            .and_then(|s| s.parse::<u64>().ok())
-            .is_none()
-        {
-            return false;
-        }
-        let slot_number_str_2 = match components.next() {
-            Some(Component::Normal(slot)) => slot,
-            _ => return false,
+        Self::from_tar_stream(tar_stream, counters)
+    }
+
+    /// Like [`Self::open`], but decompresses on a background thread.
+    pub fn open_threaded(path: &Path) -> Result<Self> {
+        Self::open_parallel(path, 1)
+    }
+
+    /// Like [`Self::open`], but decompresses on `num_threads` background threads.
+    ///
+    /// Only seekable archives are decompressed in parallel, one frame per thread.
+    /// Other archives are a single zstd (or gzip, ...) stream, which is decompressed
+    /// on one background thread regardless of `num_threads`.
+    pub fn open_parallel(path: &Path, num_threads: usize) -> Result<Self> {
+        let counters = StreamCounters::default();
+        let tar_stream = match Self::open_seekable(path, &counters, num_threads)? {
+            Some(tar_stream) => tar_stream,
+            None => TarStream::Sequential(Box::new(ReadAheadThread::spawn(Self::open_tar_stream(
+                path, &counters,
+            )?))),
         };
-        components.next().is_none() && slot_number_str_1 == slot_number_str_2
+        Self::from_tar_stream(tar_stream, counters)
     }
 
-    fn is_appendvec_file(path: &Path) -> bool {
-        let mut components = path.components();
-        if components.next() != Some(Component::Normal("accounts".as_ref())) {
-            return false;
-        }
-        let name = match components.next() {
-            Some(Component::Normal(c)) => c,
-            _ => return false,
+    /// Opens a seekable archive, decompressing frames on `num_threads` background threads,
+    /// or on the calling thread if 0.
+    fn open_seekable(
+        path: &Path,
+        counters: &StreamCounters,
+        num_threads: usize,
+    ) -> Result<Option<TarStream>> {
+        let file = counters.count_compressed(File::open(path)?);
+        let (decoder, frames): (Box<dyn ReadSeek>, usize) = if num_threads == 0 {
+            match SeekableDecoder::new(file)? {
+                Ok(decoder) => {
+                    let frames = decoder.seek_table().frames().len();
+                    (Box::new(counters.count_decompressed(decoder)), frames)
+                }
+                Err(_) => return Ok(None),
+            }
+        } else {
+            match ParallelSeekableDecoder::new(file, num_threads)? {
+                Ok(decoder) => {
+                    let frames = decoder.seek_table().frames().len();
+                    (Box::new(counters.count_decompressed(decoder)), frames)
+                }
+                Err(_) => return Ok(None),
+            }
         };
-        components.next().is_none() && parse_append_vec_name(name).is_some()
+        info!(
+            "Snapshot archive compression: seekable zstd with {} frames",
+            frames
+        );
+        Ok(Some(TarStream::Seekable(decoder)))
+    }
+
+    fn open_tar_stream(path: &Path, counters: &StreamCounters) -> Result<Box<dyn Read + Send>> {
+        let mut rd = BufReader::new(counters.count_compressed(File::open(path)?));
+        let compression = Compression::detect(&mut rd, path)?;
+        info!("Snapshot archive compression: {:?}", compression);
+        Ok(Box::new(
+            counters.count_decompressed(compression.decoder(rd)?),
+        ))
     }
 }
 
-impl ArchiveSnapshotExtractor<File> {
-    pub fn open(path: &Path) -> Result<Self> {
-        Self::from_reader(File::open(path)?)
+/// Decides whether iteration can continue after failing to process an archive member.
+///
+/// Errors raised before reading the member's data (unknown or truncated AppendVecs) are skipped
+/// with a warning unless `strict` is set, since the tar reader skips the rest of the member.
+/// I/O errors mean the stream itself is broken, so there is no next member to resume from.
+pub(crate) fn recover_member_error(
+    path: &Path,
+    err: SnapshotError,
+    strict: bool,
+) -> Option<Result<AppendVec>> {
+    match err {
+        SnapshotError::IOError(source) => Some(Err(SnapshotError::ArchiveMemberError {
+            path: path.display().to_string(),
+            source,
+        })),
+        err if strict => Some(Err(err)),
+        err => {
+            warn!("Skipping archive member {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+pub(crate) fn is_snapshot_manifest_file(path: &Path) -> bool {
+    let mut components = path.components();
+    if components.next() != Some(Component::Normal("snapshots".as_ref())) {
+        return false;
+    }
+    let slot_number_str_1 = match components.next() {
+        Some(Component::Normal(slot)) => slot,
+        _ => return false,
+    };
+    // Check if slot number file is valid u64.
+    if slot_number_str_1
+        .to_str()
+        .and_then(|s| s.parse::<u64>().ok())
+        .is_none()
+    {
+        return false;
+    }
+    let slot_number_str_2 = match components.next() {
+        Some(Component::Normal(slot)) => slot,
+        _ => return false,
+    };
+    components.next().is_none() && slot_number_str_1 == slot_number_str_2
+}
+
+pub(crate) fn is_appendvec_file(path: &Path) -> bool {
+    let mut components = path.components();
+    if components.next() != Some(Component::Normal("accounts".as_ref())) {
+        return false;
+    }
+    let name = match components.next() {
+        Some(Component::Normal(c)) => c,
+        _ => return false,
+    };
+    components.next().is_none() && parse_append_vec_name(name).is_some()
+}
+
+/// Bytes consumed from the compressed source and produced by the decoder.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamStats {
+    pub compressed_bytes: u64,
+    pub decompressed_bytes: u64,
+}
+
+impl StreamStats {
+    /// Decompressed bytes per compressed byte, or zero before anything was read.
+    pub fn ratio(&self) -> f64 {
+        if self.compressed_bytes == 0 {
+            0.0
+        } else {
+            self.decompressed_bytes as f64 / self.compressed_bytes as f64
+        }
+    }
+}
+
+/// Shared handle to the byte counters of an archive stream.
+#[derive(Clone, Default)]
+pub struct StreamCounters {
+    compressed: Arc<AtomicU64>,
+    decompressed: Arc<AtomicU64>,
+}
+
+impl StreamCounters {
+    pub fn stats(&self) -> StreamStats {
+        StreamStats {
+            compressed_bytes: self.compressed.load(Ordering::Relaxed),
+            decompressed_bytes: self.decompressed.load(Ordering::Relaxed),
+        }
+    }
+
+    fn count_compressed<R: Read>(&self, rd: R) -> CountingReader<R> {
+        CountingReader {
+            rd,
+            count: Arc::clone(&self.compressed),
+        }
+    }
+
+    fn count_decompressed<R: Read>(&self, rd: R) -> CountingReader<R> {
+        CountingReader {
+            rd,
+            count: Arc::clone(&self.decompressed),
+        }
+    }
+}
+
+struct CountingReader<R: Read> {
+    rd: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read + Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.rd.seek(pos)
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.rd.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Reads a stream on a background thread, handing out chunks through a bounded queue.
+struct ReadAheadThread {
+    rx: Receiver<std::io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ReadAheadThread {
+    const CHUNK_SIZE: usize = 1 << 20;
+    const QUEUE_DEPTH: usize = 16;
+
+    fn spawn<R: Read + Send + 'static>(mut rd: R) -> Self {
+        let (tx, rx) = sync_channel(Self::QUEUE_DEPTH);
+        std::thread::spawn(move || loop {
+            let mut chunk = vec![0u8; Self::CHUNK_SIZE];
+            match rd.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    chunk.truncate(n);
+                    if tx.send(Ok(chunk)).is_err() {
+                        break; // reader hung up
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    let _ = tx.send(Err(e));
+                    break;
+                }
+            }
+        });
+        Self {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ReadAheadThread {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0), // EOF
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
     }
 }
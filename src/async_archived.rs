@@ -0,0 +1,178 @@
+//! Async counterpart of [`crate::archived`], for services running on tokio.
+
+use crate::append_vec::DEFAULT_MAX_DATA_LEN;
+use crate::archived::{is_appendvec_file, is_snapshot_manifest_file, recover_member_error};
+use crate::loader::HttpOptions;
+use crate::sysvars::SnapshotSysvars;
+use crate::{
+    checked_append_vec_len, deserialize_from, parse_append_vec_path, AccountsDbFields, AppendVec,
+    BankSummary, DeserializableVersionedBank, Result, SerializableAccountStorageEntry,
+    SnapshotError,
+};
+use async_compression::tokio::bufread::ZstdDecoder;
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use log::info;
+use solana_sdk::clock::Slot;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader};
+use tokio_tar::{Archive, Entries, Entry};
+use tokio_util::io::StreamReader;
+
+type TarStream = Pin<Box<dyn AsyncRead + Send>>;
+
+pub type AppendVecStream<'a> = Pin<Box<dyn Stream<Item = Result<AppendVec>> + Send + 'a>>;
+
+pub trait AsyncSnapshotExtractor {
+    fn stream(&mut self) -> AppendVecStream<'_>;
+}
+
+/// Extracts account data from a .tar.zst stream without blocking the async runtime.
+pub struct AsyncArchiveSnapshotExtractor {
+    accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry>,
+    bank: BankSummary,
+    slot_range: RangeInclusive<Slot>,
+    strict: bool,
+    max_data_len: u64,
+    entries: Option<Entries<TarStream>>,
+}
+
+impl AsyncSnapshotExtractor for AsyncArchiveSnapshotExtractor {
+    fn stream(&mut self) -> AppendVecStream<'_> {
+        let accounts_db_fields = &self.accounts_db_fields;
+        let slot_range = &self.slot_range;
+        let strict = self.strict;
+        let max_data_len = self.max_data_len;
+        Box::pin(
+            stream::iter(self.entries.take())
+                .flatten()
+                .filter_map(move |entry| async move {
+                    let mut entry = match entry {
+                        Ok(x) => x,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+                    let path = match entry.path() {
+                        Ok(x) => x.into_owned(),
+                        Err(e) => return recover_member_error(Path::new("?"), e.into(), strict),
+                    };
+                    let (slot, id) = parse_append_vec_path(&path)?;
+                    if !slot_range.contains(&slot) {
+                        return None;
+                    }
+                    match Self::process_entry(accounts_db_fields, &mut entry, slot, id, strict)
+                        .await
+                    {
+                        Ok(mut append_vec) => {
+                            append_vec.set_max_data_len(max_data_len);
+                            Some(Ok(append_vec))
+                        }
+                        Err(e) => recover_member_error(&path, e, strict),
+                    }
+                }),
+        )
+    }
+}
+
+impl AsyncArchiveSnapshotExtractor {
+    /// Reads a .tar.zst stream.
+    ///
+    /// The snapshot manifest is buffered in memory before it is deserialized.
+    pub async fn from_reader<Source>(source: Source) -> Result<Self>
+    where
+        Source: AsyncRead + Send + 'static,
+    {
+        let tar_stream: TarStream = Box::pin(ZstdDecoder::new(BufReader::new(source)));
+        let mut archive = Archive::new(tar_stream);
+        let mut entries = archive.entries()?;
+
+        // Search for snapshot manifest.
+        let mut snapshot_file: Option<Vec<u8>> = None;
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            if is_snapshot_manifest_file(&path) {
+                info!("Opening snapshot manifest: {:?}", &path);
+                let mut buf = Vec::with_capacity(entry.header().size()? as usize);
+                entry.read_to_end(&mut buf).await?;
+                snapshot_file = Some(buf);
+                break;
+            } else if is_appendvec_file(&path) {
+                return Err(SnapshotError::UnexpectedAppendVec);
+            }
+        }
+        let snapshot_file = snapshot_file.ok_or(SnapshotError::NoSnapshotManifest)?;
+        let mut snapshot_file = &snapshot_file[..];
+
+        let versioned_bank: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
+        let mut bank = BankSummary::from(&versioned_bank);
+        drop(versioned_bank);
+        let accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry> =
+            deserialize_from(&mut snapshot_file)?;
+        bank.set_accounts_db_fields(&accounts_db_fields);
+
+        Ok(Self {
+            accounts_db_fields,
+            bank,
+            slot_range: 0..=Slot::MAX,
+            strict: false,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
+            entries: Some(entries),
+        })
+    }
+
+    /// Streams a .tar.zst snapshot over HTTP.
+    pub async fn download(url: &str, http_options: &HttpOptions) -> Result<Self> {
+        let resp = crate::download::get_async(url, http_options).await?;
+        let body = resp.bytes_stream().map_err(std::io::Error::other);
+        info!("Streaming snapshot from HTTP");
+        Self::from_reader(StreamReader::new(body)).await
+    }
+
+    /// Bank fields from the snapshot manifest.
+    pub fn bank(&self) -> &BankSummary {
+        &self.bank
+    }
+
+    /// Epoch schedule, rent, and fee parameters from the snapshot manifest.
+    pub fn sysvars(&self) -> &SnapshotSysvars {
+        &self.bank.sysvars
+    }
+
+    /// Only yields AppendVecs whose slot is within the range.
+    pub fn set_slot_range(&mut self, slot_range: RangeInclusive<Slot>) {
+        self.slot_range = slot_range;
+    }
+
+    /// Fails on AppendVecs that are shorter than declared in the manifest, instead of warning.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets the largest `data_len` accepted from accounts, see [`AppendVec::set_max_data_len`].
+    pub fn set_max_data_len(&mut self, max_data_len: u64) {
+        self.max_data_len = max_data_len;
+    }
+
+    async fn process_entry(
+        accounts_db_fields: &AccountsDbFields<SerializableAccountStorageEntry>,
+        entry: &mut Entry<Archive<TarStream>>,
+        slot: u64,
+        id: u64,
+        strict: bool,
+    ) -> Result<AppendVec> {
+        let known_vec = accounts_db_fields
+            .0
+            .get(&slot)
+            .and_then(|vecs| vecs.iter().find(|entry| entry.id == (id as usize)))
+            .ok_or(SnapshotError::UnexpectedAppendVec)?;
+        let len = checked_append_vec_len(
+            slot,
+            id,
+            entry.header().size()?,
+            known_vec.accounts_current_len,
+            strict,
+        )?;
+        Ok(AppendVec::new_from_async_reader(entry, len, slot, id).await?)
+    }
+}
@@ -0,0 +1,52 @@
+use log::info;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Writes each AppendVec as a `<slot>.<id>` file, like the accounts directory of an unpacked snapshot.
+pub(crate) struct AppendVecWriter {
+    dir: PathBuf,
+    append_vecs_count: u64,
+    bytes_written: u64,
+}
+
+impl AppendVecConsumer for AppendVecWriter {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        let path = self
+            .dir
+            .join(format!("{}.{}", append_vec.slot(), append_vec.id()));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        file.write_all(append_vec.as_bytes())?;
+        self.append_vecs_count += 1;
+        self.bytes_written += append_vec.len() as u64;
+        Ok(())
+    }
+}
+
+impl AppendVecWriter {
+    /// Creates the directory if it doesn't exist. Existing files are never overwritten.
+    pub(crate) fn new(dir: PathBuf) -> GenericResult<Self> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(Self {
+            dir,
+            append_vecs_count: 0,
+            bytes_written: 0,
+        })
+    }
+
+    pub(crate) fn finish(self) -> GenericResult<()> {
+        info!(
+            "Wrote {} AppendVecs ({} bytes) to {}",
+            self.append_vecs_count,
+            self.bytes_written,
+            self.dir.display()
+        );
+        Ok(())
+    }
+}
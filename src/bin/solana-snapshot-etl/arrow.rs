@@ -0,0 +1,140 @@
+use crate::columnar::AccountBatchBuilder;
+use crate::output::AccountSink;
+use arrow_ipc::writer::StreamWriter;
+use log::info;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::parallel::GenericResult;
+use std::io::Write;
+
+/// Writes accounts as an Arrow IPC stream, readable with `pyarrow.ipc.open_stream`.
+///
+/// Accounts are written in record batches of `batch_size` rows, the last one possibly shorter.
+pub(crate) struct ArrowDumper {
+    writer: StreamWriter<Box<dyn Write>>,
+    batch: AccountBatchBuilder,
+    batch_size: usize,
+    accounts_count: u64,
+}
+
+impl AccountSink for ArrowDumper {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        self.batch.append(account);
+        self.accounts_count += 1;
+        if self.batch.len() >= self.batch_size {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> GenericResult<()> {
+        if self.batch.len() > 0 {
+            self.write_batch()?;
+        }
+        self.writer.finish()?;
+        self.writer.get_mut().flush()?;
+        info!(
+            "Done writing {} accounts to Arrow stream",
+            self.accounts_count
+        );
+        Ok(())
+    }
+}
+
+impl ArrowDumper {
+    pub(crate) fn new(writer: Box<dyn Write>, batch_size: usize) -> GenericResult<Self> {
+        let batch = AccountBatchBuilder::new();
+        let writer = StreamWriter::try_new(writer, batch.schema())?;
+        Ok(Self {
+            writer,
+            batch,
+            batch_size,
+            accounts_count: 0,
+        })
+    }
+
+    fn write_batch(&mut self) -> GenericResult<()> {
+        let batch = self.batch.finish()?;
+        self.writer.write(&batch)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use arrow_ipc::reader::StreamReader;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_snapshot_etl::append_vec::{AccountMeta, StoredMeta};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Writer whose bytes can still be read after the dumper took ownership of it.
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_batches_of_batch_size() {
+        let buf = SharedBuf::default();
+        let mut dumper = ArrowDumper::new(Box::new(buf.clone()), 2).unwrap();
+        let owner = Pubkey::new_unique();
+        let pubkeys: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            let data = vec![i as u8; i];
+            let meta = StoredMeta {
+                write_version: i as u64,
+                pubkey: *pubkey,
+                data_len: data.len() as u64,
+            };
+            let account_meta = AccountMeta {
+                lamports: 1000 + i as u64,
+                owner,
+                executable: i == 4,
+                rent_epoch: 0,
+            };
+            let account = StoredAccountMeta {
+                meta: &meta,
+                account_meta: &account_meta,
+                data: &data,
+                offset: 0,
+                stored_size: 0,
+                hash: &Hash::default(),
+            };
+            dumper.write(&account).unwrap();
+        }
+        dumper.finish().unwrap();
+
+        let bytes = buf.0.borrow().clone();
+        let reader = StreamReader::try_new(&bytes[..], None).unwrap();
+        assert_eq!(reader.schema(), crate::columnar::account_schema());
+        let batches = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        let sizes: Vec<usize> = batches.iter().map(|batch| batch.num_rows()).collect();
+        assert_eq!(sizes, [2, 2, 1]);
+
+        let last = &batches[2];
+        assert_eq!(
+            last.column(0).as_string::<i32>().value(0),
+            pubkeys[4].to_string()
+        );
+        assert_eq!(
+            last.column(1).as_string::<i32>().value(0),
+            owner.to_string()
+        );
+        assert_eq!(last.column(2).as_primitive::<UInt64Type>().value(0), 1004);
+        assert_eq!(last.column(3).as_binary::<i64>().value(0), [4u8; 4]);
+        assert!(last.column(4).as_boolean().value(0));
+        assert_eq!(last.column(6).as_primitive::<UInt64Type>().value(0), 4);
+    }
+}
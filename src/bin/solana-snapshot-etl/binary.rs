@@ -0,0 +1,59 @@
+use crate::output::AccountSink;
+use log::info;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::parallel::GenericResult;
+use solana_snapshot_etl::OwnedAccount;
+use std::io::Write;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum BinaryFormat {
+    MessagePack,
+    Cbor,
+    /// Readable with `BincodeSnapshotExtractor`.
+    Bincode,
+}
+
+/// Writes accounts as a stream of length-prefixed [`OwnedAccount`] records.
+///
+/// Each record is preceded by its length as a big-endian u32,
+/// so readers can iterate the stream one record at a time.
+pub(crate) struct BinaryDumper {
+    writer: Box<dyn Write>,
+    format: BinaryFormat,
+    buf: Vec<u8>,
+}
+
+impl AccountSink for BinaryDumper {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        self.write_record(&OwnedAccount::from(account))
+    }
+
+    fn finish(mut self) -> GenericResult<()> {
+        self.writer.flush()?;
+        info!("Done writing {:?} account records", self.format);
+        Ok(())
+    }
+}
+
+impl BinaryDumper {
+    pub(crate) fn new(writer: Box<dyn Write>, format: BinaryFormat) -> Self {
+        Self {
+            writer,
+            format,
+            buf: Vec::new(),
+        }
+    }
+
+    fn write_record(&mut self, account: &OwnedAccount) -> GenericResult<()> {
+        self.buf.clear();
+        match self.format {
+            BinaryFormat::MessagePack => rmp_serde::encode::write_named(&mut self.buf, account)?,
+            BinaryFormat::Cbor => ciborium::ser::into_writer(account, &mut self.buf)?,
+            BinaryFormat::Bincode => bincode::serialize_into(&mut self.buf, account)?,
+        }
+        let len = u32::try_from(self.buf.len())?;
+        self.writer.write_all(&len.to_be_bytes())?;
+        self.writer.write_all(&self.buf)?;
+        Ok(())
+    }
+}
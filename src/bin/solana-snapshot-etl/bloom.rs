@@ -0,0 +1,44 @@
+use crate::filter::AccountFilter;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::bloom::PubkeyBloomFilter;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+
+/// Smallest possible stored account (meta, account meta, hash, no data).
+const MIN_STORED_ACCOUNT_SIZE: u64 = 136;
+
+/// Inserts the pubkeys of all accounts into a bloom filter.
+pub(crate) struct BloomWriter {
+    bloom: PubkeyBloomFilter,
+    filter: AccountFilter,
+}
+
+impl AppendVecConsumer for BloomWriter {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if self.filter.matches(&account) {
+                self.bloom.insert(&account.meta.pubkey);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl BloomWriter {
+    /// Sizes the filter for the most accounts that fit into `append_vecs_len` bytes.
+    pub(crate) fn new(
+        append_vecs_len: u64,
+        false_positive_rate: f64,
+        filter: AccountFilter,
+    ) -> Self {
+        let max_accounts = append_vecs_len / MIN_STORED_ACCOUNT_SIZE;
+        Self {
+            bloom: PubkeyBloomFilter::with_rate(max_accounts, false_positive_rate),
+            filter,
+        }
+    }
+
+    pub(crate) fn bloom(&self) -> &PubkeyBloomFilter {
+        &self.bloom
+    }
+}
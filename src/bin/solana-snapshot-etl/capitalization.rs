@@ -0,0 +1,56 @@
+use crate::error::CliError;
+use log::info;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use solana_snapshot_etl::{append_vec_accounts, BankSummary, LatestVersions};
+
+/// Sums the lamports of the live version of each account, to compare with the bank's capitalization.
+#[derive(Default)]
+pub(crate) struct CapitalizationVerifier {
+    balances: LatestVersions<u64>,
+}
+
+impl AppendVecConsumer for CapitalizationVerifier {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            self.balances.insert(
+                account.meta.pubkey,
+                account.meta.write_version,
+                account.account_meta.lamports,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl CapitalizationVerifier {
+    pub(crate) fn verify(self, bank: &BankSummary) -> GenericResult<()> {
+        let accounts_count = self.balances.len();
+        let total: u128 = self
+            .balances
+            .into_iter()
+            .map(|(_, lamports)| lamports as u128)
+            .sum();
+        info!(
+            "Summed {} lamports ({} SOL) over {} accounts",
+            total,
+            lamports_to_sol(total as u64),
+            accounts_count
+        );
+        info!(
+            "Capitalization: {} lamports ({} SOL)",
+            bank.capitalization,
+            lamports_to_sol(bank.capitalization)
+        );
+        let difference = total as i128 - bank.capitalization as i128;
+        if difference != 0 {
+            return Err(CliError::parse(format!(
+                "Summed lamports differ from capitalization by {} lamports",
+                difference
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use crate::error::CliError;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+
+/// Walks every account in every AppendVec without writing any output.
+#[derive(Default)]
+pub(crate) struct SnapshotChecker {
+    pub(crate) append_vecs_count: u64,
+    pub(crate) accounts_count: u64,
+}
+
+impl AppendVecConsumer for SnapshotChecker {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        // Accounts are packed up to the length declared in the manifest,
+        // so any parse failure before the end means the AppendVec is corrupt.
+        let mut offset = 0usize;
+        while offset < append_vec.len() {
+            match append_vec.get_account(offset) {
+                Some((_, next_offset)) => offset = next_offset,
+                None => {
+                    return Err(CliError::parse(format!(
+                        "AppendVec {}.{} (len {}): invalid account at offset {}",
+                        append_vec.slot(),
+                        append_vec.id(),
+                        append_vec.len(),
+                        offset
+                    ))
+                    .into())
+                }
+            }
+            self.accounts_count += 1;
+        }
+        self.append_vecs_count += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Typed account schema of the columnar outputs (currently `--arrow-out`).
+//!
+//! Outputs build record batches with [`AccountBatchBuilder`], so that every
+//! columnar format (e.g. a future Parquet backend) writes the same columns.
+
+use arrow_array::builder::{BooleanBuilder, LargeBinaryBuilder, StringBuilder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef};
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use std::sync::Arc;
+
+/// Columns of an account record.
+///
+/// Pubkeys are base58 strings, like the default `--encoding` of the CSV output.
+/// Data is a large binary column, since a batch may hold more than 2 GiB of it.
+pub(crate) fn account_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("pubkey", DataType::Utf8, false),
+        Field::new("owner", DataType::Utf8, false),
+        Field::new("lamports", DataType::UInt64, false),
+        Field::new("data", DataType::LargeBinary, false),
+        Field::new("executable", DataType::Boolean, false),
+        Field::new("rent_epoch", DataType::UInt64, false),
+        Field::new("write_version", DataType::UInt64, false),
+    ]))
+}
+
+/// Collects accounts into record batches of [`account_schema`].
+pub(crate) struct AccountBatchBuilder {
+    schema: SchemaRef,
+    pubkey: StringBuilder,
+    owner: StringBuilder,
+    lamports: UInt64Builder,
+    data: LargeBinaryBuilder,
+    executable: BooleanBuilder,
+    rent_epoch: UInt64Builder,
+    write_version: UInt64Builder,
+    len: usize,
+}
+
+impl AccountBatchBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            schema: account_schema(),
+            pubkey: StringBuilder::new(),
+            owner: StringBuilder::new(),
+            lamports: UInt64Builder::new(),
+            data: LargeBinaryBuilder::new(),
+            executable: BooleanBuilder::new(),
+            rent_epoch: UInt64Builder::new(),
+            write_version: UInt64Builder::new(),
+            len: 0,
+        }
+    }
+
+    pub(crate) fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    /// Number of accounts appended since the last batch.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn append(&mut self, account: &StoredAccountMeta) {
+        self.pubkey.append_value(account.meta.pubkey.to_string());
+        self.owner
+            .append_value(account.account_meta.owner.to_string());
+        self.lamports.append_value(account.account_meta.lamports);
+        self.data.append_value(account.data);
+        self.executable
+            .append_value(account.account_meta.executable);
+        self.rent_epoch
+            .append_value(account.account_meta.rent_epoch);
+        self.write_version.append_value(account.meta.write_version);
+        self.len += 1;
+    }
+
+    /// Takes the appended accounts as a record batch.
+    pub(crate) fn finish(&mut self) -> Result<RecordBatch, ArrowError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.pubkey.finish()),
+            Arc::new(self.owner.finish()),
+            Arc::new(self.lamports.finish()),
+            Arc::new(self.data.finish()),
+            Arc::new(self.executable.finish()),
+            Arc::new(self.rent_epoch.finish()),
+            Arc::new(self.write_version.finish()),
+        ];
+        self.len = 0;
+        RecordBatch::try_new(Arc::clone(&self.schema), columns)
+    }
+}
@@ -0,0 +1,120 @@
+use crate::filter::AccountFilter;
+use crate::program_names::ProgramNames;
+use log::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::collections::HashMap;
+use std::io::Write;
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Default)]
+struct OwnerSample {
+    accounts: u64,
+    data_bytes: u64,
+    compressed_bytes: u64,
+}
+
+impl OwnerSample {
+    /// Uncompressed bytes per compressed byte.
+    fn ratio(&self) -> f64 {
+        self.data_bytes as f64 / self.compressed_bytes as f64
+    }
+}
+
+/// Compresses a sample of account data with zstd to measure compressibility per owner.
+pub(crate) struct CompressibilityReport {
+    owners: HashMap<Pubkey, OwnerSample>,
+    filter: AccountFilter,
+    /// Accounts whose pubkey prefix is below this threshold are sampled.
+    threshold: u64,
+    compressor: zstd::bulk::Compressor<'static>,
+    buf: Vec<u8>,
+}
+
+impl AppendVecConsumer for CompressibilityReport {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if account.data.is_empty()
+                || !self.is_sampled(&account.meta.pubkey)
+                || !self.filter.matches(&account)
+            {
+                continue;
+            }
+            self.buf.clear();
+            self.buf
+                .reserve(zstd::zstd_safe::compress_bound(account.data.len()));
+            let compressed_len = self
+                .compressor
+                .compress_to_buffer(account.data, &mut self.buf)?;
+            let entry = self.owners.entry(account.account_meta.owner).or_default();
+            entry.accounts += 1;
+            entry.data_bytes += account.data.len() as u64;
+            entry.compressed_bytes += compressed_len as u64;
+        }
+        Ok(())
+    }
+}
+
+impl CompressibilityReport {
+    /// Samples about `sample_rate` (0 to 1) of accounts.
+    pub(crate) fn new(filter: AccountFilter, sample_rate: f64) -> GenericResult<Self> {
+        Ok(Self {
+            owners: HashMap::new(),
+            filter,
+            threshold: (sample_rate * u64::MAX as f64) as u64,
+            compressor: zstd::bulk::Compressor::new(ZSTD_LEVEL)?,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Sampling by pubkey keeps the sample stable across runs and snapshots.
+    fn is_sampled(&self, pubkey: &Pubkey) -> bool {
+        let prefix = u64::from_be_bytes(pubkey.to_bytes()[..8].try_into().unwrap());
+        prefix <= self.threshold
+    }
+
+    /// Prints owners sorted by compression ratio, most compressible first.
+    pub(crate) fn write_table<W: Write>(
+        &self,
+        mut writer: W,
+        top: Option<usize>,
+        program_names: Option<&ProgramNames>,
+    ) -> GenericResult<()> {
+        let sampled: u64 = self.owners.values().map(|sample| sample.accounts).sum();
+        info!(
+            "Compressed {} sampled accounts of {} owners",
+            sampled,
+            self.owners.len()
+        );
+        let mut owners: Vec<_> = self.owners.iter().collect();
+        owners.sort_unstable_by(|(_, a), (_, b)| b.ratio().total_cmp(&a.ratio()));
+        write!(
+            writer,
+            "{:<44} {:>12} {:>16} {:>16} {:>8}",
+            "owner", "sampled", "data_bytes", "compressed_bytes", "ratio"
+        )?;
+        if program_names.is_some() {
+            write!(writer, "  program")?;
+        }
+        writeln!(writer)?;
+        for (owner, sample) in owners.into_iter().take(top.unwrap_or(usize::MAX)) {
+            write!(
+                writer,
+                "{:<44} {:>12} {:>16} {:>16} {:>8.2}",
+                owner.to_string(),
+                sample.accounts,
+                sample.data_bytes,
+                sample.compressed_bytes,
+                sample.ratio()
+            )?;
+            if let Some(program_names) = program_names {
+                write!(writer, "  {}", program_names.name(owner))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,19 @@
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_iter;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::rc::Rc;
+
+/// Counts accounts without accessing them, so account data is never read.
+#[derive(Default)]
+pub(crate) struct AccountCounter {
+    pub(crate) append_vecs_count: u64,
+    pub(crate) accounts_count: u64,
+}
+
+impl AppendVecConsumer for AccountCounter {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        self.accounts_count += append_vec_iter(Rc::new(append_vec)).count() as u64;
+        self.append_vecs_count += 1;
+        Ok(())
+    }
+}
@@ -1,14 +1,40 @@
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::encoding::PubkeyEncoding;
+use crate::output::AccountSink;
+use crate::program_names::ProgramNames;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::info;
 use serde::Serialize;
-use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
-use solana_snapshot_etl::append_vec_iter;
+use solana_sdk::rent::Rent;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::classify::classify;
+use solana_snapshot_etl::is_rent_exempt;
+use solana_snapshot_etl::parallel::GenericResult;
 use std::io::Stdout;
-use std::rc::Rc;
+
+pub(crate) struct CsvOptions {
+    /// Column schema version (1 to 3).
+    pub(crate) schema: u8,
+    pub(crate) delimiter: u8,
+    pub(crate) buffer_size: usize,
+    pub(crate) has_headers: bool,
+    /// Comment line written before the header, without the leading `# `.
+    pub(crate) meta_comment: Option<String>,
+    pub(crate) encoding: PubkeyEncoding,
+    /// Appends an `owner_name` column if set.
+    pub(crate) program_names: Option<ProgramNames>,
+    /// Appends a `classification` column.
+    pub(crate) classify: bool,
+}
 
 pub(crate) struct CsvDumper {
     accounts_spinner: ProgressBar,
     writer: csv::Writer<Stdout>,
     accounts_count: u64,
+    schema: u8,
+    encoding: PubkeyEncoding,
+    program_names: Option<ProgramNames>,
+    classify: bool,
+    rent: Rent,
 }
 
 #[derive(Serialize)]
@@ -19,46 +45,152 @@ struct Record {
     lamports: u64,
 }
 
+#[derive(Serialize)]
+struct OwnerName {
+    owner_name: String,
+}
+
+#[derive(Serialize)]
+struct Classification {
+    classification: &'static str,
+}
+
+/// Schema version 2, which appends rent and ordering info to the v1 columns.
+#[derive(Serialize)]
+struct RecordV2 {
+    pubkey: String,
+    owner: String,
+    data_len: u64,
+    lamports: u64,
+    rent_epoch: u64,
+    write_version: u64,
+}
+
+/// Schema version 3, which appends rent exemption to the v2 columns.
+#[derive(Serialize)]
+struct RecordV3 {
+    pubkey: String,
+    owner: String,
+    data_len: u64,
+    lamports: u64,
+    rent_epoch: u64,
+    write_version: u64,
+    rent_exempt: bool,
+}
+
+impl AccountSink for CsvDumper {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        self.dump_account(account)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> GenericResult<()> {
+        self.writer.flush()?;
+        info!("Done!");
+        Ok(())
+    }
+}
+
 impl CsvDumper {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(options: CsvOptions, rent: Rent, multi_progress: &MultiProgress) -> Self {
         let spinner_style = ProgressStyle::with_template(
             "{prefix:>10.bold.dim} {spinner} rate={per_sec}/s total={human_pos}",
         )
         .unwrap();
-        let accounts_spinner = ProgressBar::new_spinner()
-            .with_style(spinner_style)
-            .with_prefix("accs");
+        let accounts_spinner = multi_progress.add(
+            ProgressBar::new_spinner()
+                .with_style(spinner_style)
+                .with_prefix("accs"),
+        );
 
-        let writer = csv::Writer::from_writer(std::io::stdout());
+        if let Some(meta_comment) = &options.meta_comment {
+            println!("# {}", meta_comment);
+        }
+        let writer = csv::WriterBuilder::new()
+            .delimiter(options.delimiter)
+            .buffer_capacity(options.buffer_size)
+            .has_headers(options.has_headers)
+            .from_writer(std::io::stdout());
 
         Self {
             accounts_spinner,
             writer,
             accounts_count: 0,
+            schema: options.schema,
+            encoding: options.encoding,
+            program_names: options.program_names,
+            classify: options.classify,
+            rent,
         }
     }
 
-    pub(crate) fn dump_append_vec(&mut self, append_vec: AppendVec) {
-        for account in append_vec_iter(Rc::new(append_vec)) {
-            let account = account.access().unwrap();
-            self.dump_account(account);
-        }
-    }
-
-    pub(crate) fn dump_account(&mut self, account: StoredAccountMeta) {
-        let record = Record {
-            pubkey: account.meta.pubkey.to_string(),
-            owner: account.account_meta.owner.to_string(),
-            data_len: account.meta.data_len,
-            lamports: account.account_meta.lamports,
-        };
-        if self.writer.serialize(record).is_err() {
-            std::process::exit(1); // if stdout closes, silently exit
-        }
+    fn dump_account(&mut self, account: &StoredAccountMeta) -> csv::Result<()> {
+        let pubkey = self.encoding.encode(&account.meta.pubkey);
+        let owner = self.encoding.encode(&account.account_meta.owner);
+        match self.schema {
+            1 => self.write_record(
+                account,
+                Record {
+                    pubkey,
+                    owner,
+                    data_len: account.meta.data_len,
+                    lamports: account.account_meta.lamports,
+                },
+            ),
+            2 => self.write_record(
+                account,
+                RecordV2 {
+                    pubkey,
+                    owner,
+                    data_len: account.meta.data_len,
+                    lamports: account.account_meta.lamports,
+                    rent_epoch: account.account_meta.rent_epoch,
+                    write_version: account.meta.write_version,
+                },
+            ),
+            _ => {
+                let rent_exempt = is_rent_exempt(&self.rent, account);
+                self.write_record(
+                    account,
+                    RecordV3 {
+                        pubkey,
+                        owner,
+                        data_len: account.meta.data_len,
+                        lamports: account.account_meta.lamports,
+                        rent_epoch: account.account_meta.rent_epoch,
+                        write_version: account.meta.write_version,
+                        rent_exempt,
+                    },
+                )
+            }
+        }?;
         self.accounts_count += 1;
         if self.accounts_count % 1024 == 0 {
             self.accounts_spinner.set_position(self.accounts_count);
         }
+        Ok(())
+    }
+
+    /// Writes a record, followed by the owner's name and the account's classification if enabled.
+    fn write_record<T: Serialize>(
+        &mut self,
+        account: &StoredAccountMeta,
+        record: T,
+    ) -> csv::Result<()> {
+        let owner_name = self.program_names.as_ref().map(|program_names| OwnerName {
+            owner_name: program_names.name(&account.account_meta.owner),
+        });
+        let classification = self.classify.then(|| Classification {
+            classification: classify(account).as_str(),
+        });
+        match (owner_name, classification) {
+            (None, None) => self.writer.serialize(record),
+            (Some(owner_name), None) => self.writer.serialize((record, owner_name)),
+            (None, Some(classification)) => self.writer.serialize((record, classification)),
+            (Some(owner_name), Some(classification)) => {
+                self.writer.serialize((record, owner_name, classification))
+            }
+        }
     }
 }
 
@@ -0,0 +1,181 @@
+use crate::output::AccountSink;
+use log::{info, warn};
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::parallel::GenericResult;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Writes the data of each account to `<dir>/<first pubkey byte as hex>/<pubkey>`.
+///
+/// Accounts without data are skipped. If an account has several versions,
+/// its file holds the latest one. AppendVecs don't come in slot order, so the
+/// versions seen so far are remembered, including those without data.
+pub(crate) struct DataFileWriter {
+    dir: PathBuf,
+    /// Maximum number of files to create.
+    limit: u64,
+    /// Maps pubkeys to the write version of their file, or of the empty version that removed it.
+    versions: HashMap<Pubkey, u64>,
+    /// Whether the shard directory of each first byte was created.
+    shards: [bool; 256],
+    files_count: u64,
+    bytes_written: u64,
+    /// Accounts not written because the limit was reached.
+    skipped_count: u64,
+}
+
+impl AccountSink for DataFileWriter {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        let pubkey = account.meta.pubkey;
+        let write_version = account.meta.write_version;
+        let previous = self.versions.get(&pubkey).copied();
+        if previous.is_some_and(|previous| previous > write_version) {
+            return Ok(());
+        }
+        let path = self.path(&pubkey);
+        if account.data.is_empty() {
+            // A newer empty version, e.g. of a closed account, replaces the file.
+            // It is remembered even without a file, since older versions may come later.
+            self.versions.insert(pubkey, write_version);
+            if previous.is_some() && path.exists() {
+                std::fs::remove_file(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                self.files_count -= 1;
+            }
+            return Ok(());
+        }
+        let is_new_file = previous.is_none() || !path.exists();
+        if is_new_file && self.files_count >= self.limit {
+            if self.skipped_count == 0 {
+                warn!(
+                    "Reached the limit of {} data files, skipping the remaining accounts (see --extract-data-limit)",
+                    self.limit
+                );
+            }
+            self.skipped_count += 1;
+            return Ok(());
+        }
+        let shard = pubkey.as_ref()[0] as usize;
+        if !self.shards[shard] {
+            let shard_dir = path.parent().unwrap();
+            std::fs::create_dir_all(shard_dir)
+                .map_err(|e| format!("{}: {}", shard_dir.display(), e))?;
+            self.shards[shard] = true;
+        }
+        // Files from earlier runs are never overwritten, only older versions written by this one.
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(previous.is_none())
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        file.write_all(account.data)?;
+        self.versions.insert(pubkey, write_version);
+        if is_new_file {
+            self.files_count += 1;
+        }
+        self.bytes_written += account.data.len() as u64;
+        Ok(())
+    }
+
+    fn finish(self) -> GenericResult<()> {
+        info!(
+            "Wrote the data of {} accounts ({} bytes) to {}",
+            self.files_count,
+            self.bytes_written,
+            self.dir.display()
+        );
+        if self.skipped_count > 0 {
+            warn!(
+                "Skipped {} accounts over the limit of {} data files",
+                self.skipped_count, self.limit
+            );
+        }
+        Ok(())
+    }
+}
+
+impl DataFileWriter {
+    /// Creates the directory if it doesn't exist.
+    pub(crate) fn new(dir: PathBuf, limit: u64) -> GenericResult<Self> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(Self {
+            dir,
+            limit,
+            versions: HashMap::new(),
+            shards: [false; 256],
+            files_count: 0,
+            bytes_written: 0,
+            skipped_count: 0,
+        })
+    }
+
+    fn path(&self, pubkey: &Pubkey) -> PathBuf {
+        self.dir
+            .join(format!("{:02x}", pubkey.as_ref()[0]))
+            .join(pubkey.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_snapshot_etl::append_vec::AppendVec;
+    use solana_snapshot_etl::{append_vec_accounts, OwnedAccount};
+
+    fn account(pubkey: Pubkey, write_version: u64, data: Vec<u8>) -> OwnedAccount {
+        OwnedAccount {
+            pubkey,
+            owner: Pubkey::new_unique(),
+            lamports: 1,
+            data,
+            executable: false,
+            rent_epoch: 0,
+            write_version,
+        }
+    }
+
+    /// Writes the accounts in order, each one from its own AppendVec.
+    fn write_all(writer: &mut DataFileWriter, accounts: &[OwnedAccount]) {
+        for (id, account) in accounts.iter().enumerate() {
+            let append_vec =
+                AppendVec::new_from_accounts(std::slice::from_ref(account), 0, id as u64).unwrap();
+            for account in append_vec_accounts(&append_vec) {
+                writer.write(&account).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn newer_empty_version_removes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = DataFileWriter::new(dir.path().to_path_buf(), 10).unwrap();
+        let pubkey = Pubkey::new_unique();
+        write_all(&mut writer, &[account(pubkey, 1, vec![1, 2, 3])]);
+        let path = writer.path(&pubkey);
+        assert_eq!(std::fs::read(&path).unwrap(), [1, 2, 3]);
+
+        write_all(&mut writer, &[account(pubkey, 2, vec![])]);
+        assert!(!path.exists());
+        assert_eq!(writer.files_count, 0);
+    }
+
+    #[test]
+    fn older_version_after_newer_empty_version_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = DataFileWriter::new(dir.path().to_path_buf(), 10).unwrap();
+        let pubkey = Pubkey::new_unique();
+        write_all(
+            &mut writer,
+            &[
+                account(pubkey, 2, vec![]),
+                account(pubkey, 1, vec![1, 2, 3]),
+            ],
+        );
+        assert!(!writer.path(&pubkey).exists());
+        assert_eq!(writer.files_count, 0);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::encoding::PubkeyEncoding;
+use serde::Serialize;
+use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
+use solana_snapshot_etl::hash::{account_data_hash, HASH_BYTES};
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use solana_snapshot_etl::{append_vec_accounts, LatestVersions};
+use std::io::Write;
+
+#[derive(Clone, Copy)]
+struct AccountVersion {
+    lamports: u64,
+    data_hash: [u8; HASH_BYTES],
+}
+
+/// Compact index of the latest version of each account in a snapshot.
+#[derive(Default)]
+pub(crate) struct AccountIndex {
+    accounts: LatestVersions<AccountVersion>,
+}
+
+impl AppendVecConsumer for AccountIndex {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            self.insert(&account);
+        }
+        Ok(())
+    }
+}
+
+impl AccountIndex {
+    fn insert(&mut self, account: &StoredAccountMeta) {
+        let version = AccountVersion {
+            lamports: account.account_meta.lamports,
+            data_hash: account_data_hash(account.data),
+        };
+        self.accounts
+            .insert(account.meta.pubkey, account.meta.write_version, version);
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    /// Removes zero-lamport accounts, which are deleted.
+    fn into_live(self) -> LatestVersions<AccountVersion> {
+        let mut accounts = self.accounts;
+        accounts.retain(|_, version| version.lamports > 0);
+        accounts
+    }
+}
+
+#[derive(Serialize)]
+struct DiffRecord {
+    pubkey: String,
+    change_type: &'static str,
+    old_lamports: Option<u64>,
+    new_lamports: Option<u64>,
+}
+
+#[derive(Default)]
+pub(crate) struct DiffStats {
+    pub(crate) added: u64,
+    pub(crate) removed: u64,
+    pub(crate) changed: u64,
+}
+
+/// Writes the accounts that were added, removed, or changed between two snapshots as CSV.
+pub(crate) fn write_diff<W: Write>(
+    old: AccountIndex,
+    new: AccountIndex,
+    encoding: PubkeyEncoding,
+    writer: W,
+) -> GenericResult<DiffStats> {
+    let mut old = old.into_live();
+    let new = new.into_live();
+    let mut writer = csv::Writer::from_writer(writer);
+    let mut stats = DiffStats::default();
+    for (pubkey, new_version) in new {
+        let record = match old.remove(&pubkey) {
+            None => {
+                stats.added += 1;
+                DiffRecord {
+                    pubkey: encoding.encode(&pubkey),
+                    change_type: "added",
+                    old_lamports: None,
+                    new_lamports: Some(new_version.lamports),
+                }
+            }
+            Some(old_version)
+                if old_version.lamports != new_version.lamports
+                    || old_version.data_hash != new_version.data_hash =>
+            {
+                stats.changed += 1;
+                DiffRecord {
+                    pubkey: encoding.encode(&pubkey),
+                    change_type: "changed",
+                    old_lamports: Some(old_version.lamports),
+                    new_lamports: Some(new_version.lamports),
+                }
+            }
+            Some(_) => continue,
+        };
+        writer.serialize(record)?;
+    }
+    for (pubkey, old_version) in old {
+        writer.serialize(DiffRecord {
+            pubkey: encoding.encode(&pubkey),
+            change_type: "removed",
+            old_lamports: Some(old_version.lamports),
+            new_lamports: None,
+        })?;
+        stats.removed += 1;
+    }
+    writer.flush()?;
+    Ok(stats)
+}
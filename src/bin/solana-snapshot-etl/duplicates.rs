@@ -0,0 +1,69 @@
+use log::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Counts pubkeys that are stored in more than one version.
+#[derive(Default)]
+pub(crate) struct DuplicateReport {
+    accounts_count: u64,
+    counts: HashMap<Pubkey, u32>,
+    /// (slot, write_version) of each stored version, only kept when listing duplicates.
+    versions: Option<HashMap<Pubkey, Vec<(u64, u64)>>>,
+}
+
+impl AppendVecConsumer for DuplicateReport {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        let slot = append_vec.slot();
+        for account in append_vec_accounts(&append_vec) {
+            self.accounts_count += 1;
+            *self.counts.entry(account.meta.pubkey).or_default() += 1;
+            if let Some(versions) = &mut self.versions {
+                versions
+                    .entry(account.meta.pubkey)
+                    .or_default()
+                    .push((slot, account.meta.write_version));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DuplicateReport {
+    /// Creates a report that also remembers the slot and write version of every account.
+    pub(crate) fn with_versions() -> Self {
+        Self {
+            versions: Some(HashMap::new()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn log_summary(&self) {
+        let duplicated = self.counts.values().filter(|&&n| n > 1).count();
+        info!(
+            "{} accounts, {} unique pubkeys, {} pubkeys with more than one version",
+            self.accounts_count,
+            self.counts.len(),
+            duplicated
+        );
+    }
+
+    /// Lists every version of duplicated pubkeys, the live version last.
+    pub(crate) fn write_versions<W: Write>(&mut self, mut writer: W) -> GenericResult<()> {
+        let versions = match &mut self.versions {
+            Some(versions) => versions,
+            None => return Ok(()),
+        };
+        writeln!(writer, "pubkey,slot,write_version")?;
+        for (pubkey, versions) in versions.iter_mut().filter(|(_, v)| v.len() > 1) {
+            versions.sort_unstable_by_key(|&(slot, write_version)| (write_version, slot));
+            for (slot, write_version) in versions.iter() {
+                writeln!(writer, "{},{},{}", pubkey, slot, write_version)?;
+            }
+        }
+        Ok(())
+    }
+}
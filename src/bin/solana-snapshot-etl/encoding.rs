@@ -0,0 +1,21 @@
+use clap::ValueEnum;
+use solana_sdk::pubkey::Pubkey;
+
+/// Text representation of pubkeys in CSV outputs.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub(crate) enum PubkeyEncoding {
+    #[default]
+    Base58,
+    Base64,
+    Hex,
+}
+
+impl PubkeyEncoding {
+    pub(crate) fn encode(self, pubkey: &Pubkey) -> String {
+        match self {
+            Self::Base58 => pubkey.to_string(),
+            Self::Base64 => base64::encode(pubkey),
+            Self::Hex => hex::encode(pubkey),
+        }
+    }
+}
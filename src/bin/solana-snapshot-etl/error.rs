@@ -0,0 +1,56 @@
+use std::error::Error;
+use thiserror::Error;
+
+type BoxError = Box<dyn Error>;
+
+/// Error of a run, by the stage that failed, so that scripts can tell them apart by exit code.
+#[derive(Debug, Error)]
+pub(crate) enum CliError {
+    /// Invalid arguments or files referenced by them, before the snapshot is opened.
+    #[error("{0}")]
+    Config(BoxError),
+    /// The snapshot couldn't be opened, or its manifest couldn't be read.
+    #[error("{0}")]
+    Load(BoxError),
+    /// The accounts couldn't be read, or failed verification.
+    #[error("{0}")]
+    Parse(BoxError),
+    /// An output couldn't be opened or written.
+    #[error("{0}")]
+    Output(BoxError),
+}
+
+impl CliError {
+    pub(crate) fn config(e: impl Into<BoxError>) -> Self {
+        Self::Config(e.into())
+    }
+
+    pub(crate) fn load(e: impl Into<BoxError>) -> Self {
+        Self::Load(e.into())
+    }
+
+    pub(crate) fn parse(e: impl Into<BoxError>) -> Self {
+        Self::Parse(e.into())
+    }
+
+    /// Wraps an output error, unless it already is a [`CliError`].
+    ///
+    /// Outputs only return boxed errors, so the ones failing for another
+    /// reason (e.g. `--check` or capitalization verification) wrap their errors first.
+    pub(crate) fn output(e: impl Into<BoxError>) -> Self {
+        match e.into().downcast::<CliError>() {
+            Ok(e) => *e,
+            Err(e) => Self::Output(e),
+        }
+    }
+
+    /// 2 like usage errors reported by clap, the other stages follow.
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Self::Config(_) => 2,
+            Self::Load(_) => 3,
+            Self::Parse(_) => 4,
+            Self::Output(_) => 5,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use std::collections::HashSet;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Selects accounts by whether they hold any lamports.
+///
+/// Zero-lamport accounts are tombstones of deleted accounts,
+/// which may still carry data in raw snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum EmptyFilter {
+    /// Keep all accounts.
+    #[default]
+    Include,
+    /// Drop zero-lamport accounts.
+    Skip,
+    /// Keep only zero-lamport accounts.
+    Only,
+}
+
+/// Predicates that decide which accounts get written to the output.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AccountFilter {
+    pub(crate) empty: EmptyFilter,
+    /// Only keep accounts owned by these programs, if not empty.
+    pub(crate) owners: HashSet<Pubkey>,
+    /// Drop accounts owned by these programs, takes precedence over `owners`.
+    pub(crate) excluded_owners: HashSet<Pubkey>,
+    /// Drop accounts with less data than this.
+    pub(crate) min_data_len: Option<u64>,
+    /// Drop accounts with more data than this.
+    pub(crate) max_data_len: Option<u64>,
+    /// Only keep accounts whose executable flag is this, if set.
+    pub(crate) executable: Option<bool>,
+}
+
+impl AccountFilter {
+    pub(crate) fn matches(&self, account: &StoredAccountMeta) -> bool {
+        if !self.owners.is_empty() && !self.owners.contains(&account.account_meta.owner) {
+            return false;
+        }
+        if self.excluded_owners.contains(&account.account_meta.owner) {
+            return false;
+        }
+        let data_len = account.meta.data_len;
+        if self.min_data_len.is_some_and(|min| data_len < min)
+            || self.max_data_len.is_some_and(|max| data_len > max)
+        {
+            return false;
+        }
+        if self
+            .executable
+            .is_some_and(|executable| account.account_meta.executable != executable)
+        {
+            return false;
+        }
+        let is_empty = account.account_meta.lamports == 0;
+        match self.empty {
+            EmptyFilter::Include => true,
+            EmptyFilter::Skip => !is_empty,
+            EmptyFilter::Only => is_empty,
+        }
+    }
+}
+
+/// Reads a newline-delimited list of base58 pubkeys.
+///
+/// Blank lines and lines starting with `#` are ignored.
+pub(crate) fn read_pubkey_file(path: &Path) -> Result<Vec<Pubkey>, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            Pubkey::from_str(line).map_err(|e| {
+                format!(
+                    "{}:{}: invalid pubkey {:?}: {}",
+                    path.display(),
+                    line_number,
+                    line,
+                    e
+                )
+                .into()
+            })
+        })
+        .collect()
+}
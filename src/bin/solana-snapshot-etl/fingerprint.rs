@@ -0,0 +1,72 @@
+use crate::filter::AccountFilter;
+use log::info;
+use solana_sdk::hash::{hashv, Hash};
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::io::Write;
+
+/// Order-independent digest of all stored account versions.
+///
+/// Each `(pubkey, write_version, data hash)` tuple is hashed with SHA-256, and the hashes
+/// are summed as 256-bit integers (wrapping). Unlike XOR, a sum doesn't cancel out
+/// accounts that are stored twice, so the digest is a hash of the multiset of versions.
+pub(crate) struct Fingerprint {
+    sum: [u64; 4],
+    accounts_count: u64,
+    filter: AccountFilter,
+}
+
+impl AppendVecConsumer for Fingerprint {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if !self.filter.matches(&account) {
+                continue;
+            }
+            let data_hash = hashv(&[account.data]);
+            let hash = hashv(&[
+                account.meta.pubkey.as_ref(),
+                &account.meta.write_version.to_le_bytes(),
+                data_hash.as_ref(),
+            ]);
+            self.add(&hash);
+            self.accounts_count += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Fingerprint {
+    pub(crate) fn new(filter: AccountFilter) -> Self {
+        Self {
+            sum: [0; 4],
+            accounts_count: 0,
+            filter,
+        }
+    }
+
+    /// Adds a hash, read as a little-endian integer, to the sum.
+    fn add(&mut self, hash: &Hash) {
+        let mut carry = false;
+        for (limb, bytes) in self.sum.iter_mut().zip(hash.as_ref().chunks_exact(8)) {
+            let (sum, overflow_1) =
+                limb.overflowing_add(u64::from_le_bytes(bytes.try_into().unwrap()));
+            let (sum, overflow_2) = sum.overflowing_add(carry as u64);
+            *limb = sum;
+            carry = overflow_1 || overflow_2;
+        }
+    }
+
+    /// Prints the digest as hex.
+    pub(crate) fn write_digest<W: Write>(&self, mut writer: W) -> GenericResult<()> {
+        info!("Fingerprinted {} accounts", self.accounts_count);
+        let digest: String = self
+            .sum
+            .iter()
+            .flat_map(|limb| limb.to_le_bytes())
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        writeln!(writer, "{}", digest)?;
+        Ok(())
+    }
+}
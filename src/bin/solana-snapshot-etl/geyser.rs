@@ -1,68 +1,109 @@
 // TODO add multi-threading
 
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::output::AccountSink;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::info;
 use solana_geyser_plugin_interface::geyser_plugin_interface::{
-    GeyserPlugin, ReplicaAccountInfoV2, ReplicaAccountInfoVersions,
+    GeyserPlugin, ReplicaAccountInfo, ReplicaAccountInfoV2, ReplicaAccountInfoVersions,
 };
 use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
-use solana_snapshot_etl::append_vec_iter;
-use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use solana_snapshot_etl::parallel::GenericResult;
 use std::error::Error;
-use std::rc::Rc;
 
 pub(crate) struct GeyserDumper {
     accounts_spinner: ProgressBar,
-    plugin: Box<dyn GeyserPlugin>,
+    plugins: Vec<Box<dyn GeyserPlugin>>,
     accounts_count: u64,
+    account_version: u8,
+    /// Slot of the AppendVec being dumped.
+    slot: u64,
 }
 
-impl AppendVecConsumer for GeyserDumper {
-    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
-        for account in append_vec_iter(Rc::new(append_vec)) {
-            let account = account.access().unwrap();
-            self.dump_account(account)?;
-        }
+impl AccountSink for GeyserDumper {
+    fn begin_append_vec(&mut self, append_vec: &AppendVec) -> GenericResult<()> {
+        self.slot = append_vec.slot();
+        Ok(())
+    }
+
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        self.dump_account(account, self.slot)
+    }
+
+    fn finish(self) -> GenericResult<()> {
+        drop(self);
+        info!("Done notifying Geyser plugins");
         Ok(())
     }
 }
 
 impl GeyserDumper {
-    pub(crate) fn new(plugin: Box<dyn GeyserPlugin>) -> Self {
+    /// Creates a dumper sending `ReplicaAccountInfo` structs of the given version (1 or 2)
+    /// to each plugin.
+    ///
+    /// Solana 1.11 has no V3, newer plugins have to be built against this interface version.
+    pub(crate) fn new(
+        plugins: Vec<Box<dyn GeyserPlugin>>,
+        account_version: u8,
+        multi_progress: &MultiProgress,
+    ) -> Self {
         // TODO dedup spinner definitions
         let spinner_style = ProgressStyle::with_template(
             "{prefix:>10.bold.dim} {spinner} rate={per_sec}/s total={human_pos}",
         )
         .unwrap();
-        let accounts_spinner = ProgressBar::new_spinner()
-            .with_style(spinner_style)
-            .with_prefix("accs");
+        let accounts_spinner = multi_progress.add(
+            ProgressBar::new_spinner()
+                .with_style(spinner_style)
+                .with_prefix("accs"),
+        );
 
         Self {
             accounts_spinner,
-            plugin,
+            plugins,
             accounts_count: 0,
+            account_version,
+            slot: 0,
         }
     }
 
-    pub(crate) fn dump_account(
+    fn dump_account(
         &mut self,
-        account: StoredAccountMeta,
+        account: &StoredAccountMeta,
+        slot: u64,
     ) -> Result<(), Box<dyn Error>> {
-        let slot = 0u64; // TODO fix slot number
-        self.plugin.update_account(
-            ReplicaAccountInfoVersions::V0_0_2(&ReplicaAccountInfoV2 {
-                pubkey: account.meta.pubkey.as_ref(),
-                lamports: account.account_meta.lamports,
-                owner: account.account_meta.owner.as_ref(),
-                executable: account.account_meta.executable,
-                rent_epoch: account.account_meta.rent_epoch,
-                data: account.data,
-                write_version: account.meta.write_version,
-                txn_signature: None,
-            }),
-            slot,
-            /* is_startup */ false,
-        )?;
+        let info = ReplicaAccountInfoV2 {
+            pubkey: account.meta.pubkey.as_ref(),
+            lamports: account.account_meta.lamports,
+            owner: account.account_meta.owner.as_ref(),
+            executable: account.account_meta.executable,
+            rent_epoch: account.account_meta.rent_epoch,
+            data: account.data,
+            write_version: account.meta.write_version,
+            txn_signature: None,
+        };
+        for plugin in &mut self.plugins {
+            if self.account_version == 1 {
+                plugin.update_account(
+                    ReplicaAccountInfoVersions::V0_0_1(&ReplicaAccountInfo {
+                        pubkey: info.pubkey,
+                        lamports: info.lamports,
+                        owner: info.owner,
+                        executable: info.executable,
+                        rent_epoch: info.rent_epoch,
+                        data: info.data,
+                        write_version: info.write_version,
+                    }),
+                    slot,
+                    /* is_startup */ false,
+                )?;
+            } else {
+                plugin.update_account(
+                    ReplicaAccountInfoVersions::V0_0_2(&info),
+                    slot,
+                    /* is_startup */ false,
+                )?;
+            }
+        }
         self.accounts_count += 1;
         if self.accounts_count % 1024 == 0 {
             self.accounts_spinner.set_position(self.accounts_count);
@@ -0,0 +1,120 @@
+use crate::mpl_metadata;
+use borsh::BorshDeserialize;
+use solana_program::bpf_loader_upgradeable::UpgradeableLoaderState;
+use solana_program::{bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::rent::Rent;
+use solana_snapshot_etl::OwnedAccount;
+use std::io::{self, Write};
+
+/// Prints all fields of an account, a decoded view of known account types, and a hexdump of its data.
+pub(crate) fn write_account<W: Write>(
+    mut w: W,
+    account: &OwnedAccount,
+    rent: &Rent,
+) -> io::Result<()> {
+    writeln!(w, "pubkey:        {}", account.pubkey)?;
+    writeln!(w, "owner:         {}", account.owner)?;
+    writeln!(
+        w,
+        "lamports:      {}{}",
+        account.lamports,
+        if account.lamports == 0 {
+            " (deleted)"
+        } else {
+            ""
+        }
+    )?;
+    writeln!(w, "data_len:      {}", account.data.len())?;
+    writeln!(w, "executable:    {}", account.executable)?;
+    writeln!(w, "rent_epoch:    {}", account.rent_epoch)?;
+    writeln!(
+        w,
+        "rent_exempt:   {}",
+        account.rent_epoch == u64::MAX || rent.is_exempt(account.lamports, account.data.len())
+    )?;
+    writeln!(w, "write_version: {}", account.write_version)?;
+    if let Some(decoded) = decode(account) {
+        writeln!(w)?;
+        writeln!(w, "{}", decoded)?;
+    }
+    if !account.data.is_empty() {
+        writeln!(w)?;
+        write_hexdump(&mut w, &account.data)?;
+    }
+    Ok(())
+}
+
+/// Decodes SPL Token, Token Metadata, and BPF loader accounts.
+fn decode(account: &OwnedAccount) -> Option<String> {
+    let data = &account.data[..];
+    if account.owner == spl_token::id() {
+        return match data.len() {
+            spl_token::state::Account::LEN => spl_token::state::Account::unpack(data)
+                .ok()
+                .map(|a| format!("{:#?}", a)),
+            spl_token::state::Mint::LEN => spl_token::state::Mint::unpack(data)
+                .ok()
+                .map(|m| format!("{:#?}", m)),
+            spl_token::state::Multisig::LEN => spl_token::state::Multisig::unpack(data)
+                .ok()
+                .map(|m| format!("{:#?}", m)),
+            _ => None,
+        };
+    }
+    if account.owner == mpl_metadata::id() {
+        let mut data = data;
+        return match mpl_metadata::AccountKey::deserialize(&mut data).ok()? {
+            mpl_metadata::AccountKey::MetadataV1 => {
+                let meta = mpl_metadata::Metadata::deserialize(&mut data).ok()?;
+                Some(format!(
+                    "Metadata {{\n    update_authority: {},\n    mint: {},\n    name: {:?},\n    symbol: {:?},\n    uri: {:?},\n    seller_fee_basis_points: {},\n    primary_sale_happened: {},\n    is_mutable: {},\n}}",
+                    meta.update_authority,
+                    meta.mint,
+                    meta.data.name.0.trim_end_matches('\0'),
+                    meta.data.symbol.0.trim_end_matches('\0'),
+                    meta.data.uri.0.trim_end_matches('\0'),
+                    meta.data.seller_fee_basis_points,
+                    meta.primary_sale_happened,
+                    meta.is_mutable,
+                ))
+            }
+            _ => None,
+        };
+    }
+    if bpf_loader::check_id(&account.owner) || bpf_loader_deprecated::check_id(&account.owner) {
+        return account
+            .executable
+            .then(|| format!("Program (ELF, {} bytes)", data.len()));
+    }
+    if bpf_loader_upgradeable::check_id(&account.owner) {
+        let state: UpgradeableLoaderState = bincode::deserialize(data).ok()?;
+        return Some(format!("{:#?}", state));
+    }
+    None
+}
+
+/// Writes 16 bytes per line, with the offset, hex bytes, and printable ASCII.
+fn write_hexdump<W: Write>(mut w: W, data: &[u8]) -> io::Result<()> {
+    for (i, chunk) in data.chunks(16).enumerate() {
+        write!(w, "{:08x} ", i * 16)?;
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => write!(w, " {:02x}", b)?,
+                None => write!(w, "   ")?,
+            }
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        writeln!(w, "  |{}|", ascii)?;
+    }
+    Ok(())
+}
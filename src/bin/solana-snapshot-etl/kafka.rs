@@ -0,0 +1,111 @@
+use crate::output::AccountSink;
+use log::{error, info};
+use rdkafka::config::ClientConfig;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::ClientContext;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::parallel::GenericResult;
+use solana_snapshot_etl::OwnedAccount;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+pub(crate) struct KafkaOptions {
+    pub(crate) brokers: String,
+    pub(crate) topic: String,
+    /// Maximum number of messages batched into one request.
+    pub(crate) batch_size: u32,
+    /// librdkafka compression codec (none, gzip, snappy, lz4, zstd).
+    pub(crate) compression: String,
+}
+
+/// Publishes one message per account, keyed by pubkey, with a bincode-encoded [`OwnedAccount`].
+pub(crate) struct KafkaDumper {
+    producer: BaseProducer<DeliveryCounter>,
+    topic: String,
+    accounts_count: u64,
+}
+
+/// Counts messages that could not be delivered.
+#[derive(Default)]
+struct DeliveryCounter {
+    failed: AtomicU64,
+}
+
+impl ClientContext for DeliveryCounter {}
+
+impl ProducerContext for DeliveryCounter {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        if let Err((e, _)) = delivery_result {
+            if self.failed.fetch_add(1, Ordering::Relaxed) == 0 {
+                error!("Failed to deliver Kafka message: {}", e);
+            }
+        }
+    }
+}
+
+impl AccountSink for KafkaDumper {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        let payload = bincode::serialize(&OwnedAccount::from(account))?;
+        self.produce(account.meta.pubkey.as_ref(), &payload)
+    }
+
+    /// Waits until all messages are delivered.
+    fn finish(self) -> GenericResult<()> {
+        self.producer.flush(Duration::from_secs(60));
+        let failed = self.producer.context().failed.load(Ordering::Relaxed);
+        let in_flight = self.producer.in_flight_count();
+        if failed > 0 || in_flight > 0 {
+            return Err(format!(
+                "{} of {} Kafka messages were not delivered",
+                failed + in_flight as u64,
+                self.accounts_count
+            )
+            .into());
+        }
+        info!("Delivered {} Kafka messages", self.accounts_count);
+        Ok(())
+    }
+}
+
+impl KafkaDumper {
+    pub(crate) fn new(options: KafkaOptions) -> GenericResult<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", &options.brokers)
+            .set("batch.num.messages", options.batch_size.to_string())
+            .set("compression.type", &options.compression)
+            .create_with_context(DeliveryCounter::default())?;
+        Ok(Self {
+            producer,
+            topic: options.topic,
+            accounts_count: 0,
+        })
+    }
+
+    fn produce(&mut self, key: &[u8], payload: &[u8]) -> GenericResult<()> {
+        let mut record = BaseRecord::to(&self.topic).key(key).payload(payload);
+        loop {
+            match self.producer.send(record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), rejected)) => {
+                    // Wait for in-flight messages to make room in the queue.
+                    self.producer.poll(Duration::from_millis(100));
+                    record = rejected;
+                }
+                Err((e, _)) => return Err(e.into()),
+            }
+        }
+        self.producer.poll(Duration::ZERO);
+        self.accounts_count += 1;
+        Ok(())
+    }
+}
+
+impl Drop for KafkaDumper {
+    fn drop(&mut self) {
+        // No-op after finish, delivers what is left if the export failed midway.
+        self.producer.flush(Duration::from_secs(60));
+    }
+}
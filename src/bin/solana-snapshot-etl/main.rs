@@ -1,124 +1,1460 @@
-use crate::csv::CsvDumper;
+use crate::append_vecs::AppendVecWriter;
+use crate::binary::{BinaryDumper, BinaryFormat};
+use crate::bloom::BloomWriter;
+use crate::capitalization::CapitalizationVerifier;
+use crate::check::SnapshotChecker;
+use crate::compressibility::CompressibilityReport;
+use crate::count::AccountCounter;
+use crate::csv::{CsvDumper, CsvOptions};
+use crate::data_files::DataFileWriter;
+use crate::diff::AccountIndex;
+use crate::duplicates::DuplicateReport;
+use crate::encoding::PubkeyEncoding;
+use crate::error::CliError;
+use crate::filter::{read_pubkey_file, AccountFilter, EmptyFilter};
+use crate::fingerprint::Fingerprint;
+#[cfg(feature = "geyser")]
 use crate::geyser::GeyserDumper;
+#[cfg(feature = "geyser")]
 use crate::geyser_plugin::load_plugin;
+use crate::metrics::Metrics;
+use crate::output::{run_outputs, with_finish, with_sink, Output};
+use crate::program_names::ProgramNames;
 use crate::programs::ProgramDumper;
+use crate::rent_histogram::RentHistogram;
+use crate::size_report::SizeReport;
+#[cfg(feature = "sqlite")]
 use crate::sqlite::SqliteIndexer;
-use clap::{ArgGroup, Parser};
-use indicatif::{ProgressBar, ProgressBarIter, ProgressStyle};
-use log::{error, info};
-use reqwest::blocking::Response;
-use solana_snapshot_etl::archived::ArchiveSnapshotExtractor;
-use solana_snapshot_etl::parallel::AppendVecConsumer;
-use solana_snapshot_etl::unpacked::UnpackedSnapshotExtractor;
-use solana_snapshot_etl::{AppendVecIterator, ReadProgressTracking, SnapshotExtractor};
+use crate::timings::Timings;
+use crate::top_accounts::{TopAccounts, TopAccountsKey};
+use crate::verify::BankHashVerifier;
+use crate::wallets::WalletReport;
+use clap::{ArgGroup, Parser, ValueEnum};
+use indicatif::{
+    HumanBytes, MultiProgress, ProgressBar, ProgressBarIter, ProgressDrawTarget, ProgressStyle,
+};
+use log::{error, info, warn};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction::MAX_PERMITTED_DATA_LENGTH;
+use solana_snapshot_etl::archived::{StreamCounters, StreamStats};
+use solana_snapshot_etl::loader::{ExtractorBuilder, HttpOptions, SupportedLoader};
+use solana_snapshot_etl::parallel::{par_iter_append_vecs, AppendVecConsumer};
+#[cfg(feature = "sqlite")]
+use solana_snapshot_etl::status_cache::StatusCacheEntry;
+use solana_snapshot_etl::{
+    find_account, AppendVecIterator, BankSummary, NullReadProgressTracking, ReadProgressTracking,
+    SnapshotExtractor,
+};
+use std::collections::HashSet;
 use std::fs::{File, OpenOptions};
-use std::io::{stdout, IoSliceMut, Read, Write};
+use std::io::{stdout, BufWriter, IoSliceMut, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+mod append_vecs;
+#[cfg(feature = "arrow")]
+mod arrow;
+mod binary;
+mod bloom;
+mod capitalization;
+mod check;
+#[cfg(feature = "arrow")]
+mod columnar;
+mod compressibility;
+mod count;
 mod csv;
+mod data_files;
+mod diff;
+mod duplicates;
+mod encoding;
+mod error;
+mod filter;
+mod fingerprint;
+#[cfg(feature = "geyser")]
 mod geyser;
+#[cfg(feature = "geyser")]
 mod geyser_plugin;
+mod inspect;
+mod interrupt;
+#[cfg(feature = "kafka")]
+mod kafka;
+// Counters are only registered when the endpoint can be served.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+mod metrics;
+// Only account keys are decoded without the SQLite3 indexer.
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
 mod mpl_metadata;
+#[cfg(feature = "rbpf")]
+mod opcode_stats;
+mod output;
+mod program_names;
 mod programs;
+mod rent_histogram;
+mod size_report;
+#[cfg(feature = "sqlite")]
 mod sqlite;
+mod timings;
+mod top_accounts;
+mod verify;
+mod wallets;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 #[clap(group(
     ArgGroup::new("action")
         .required(true)
-        .args(&["csv", "geyser", "sqlite-out", "programs-out"]),
+        .multiple(true)
+        .args(&["csv", "geyser", "sqlite-out", "programs-out", "opcode-stats", "check", "diff-against", "size-report", "verify-bank-hash", "report-duplicates", "kafka", "msgpack-out", "cbor-out", "bincode-out", "arrow-out", "bloom-out", "count-only", "inspect", "wallets", "compressibility", "verify-capitalization", "fingerprint", "rent-histogram", "append-vecs-out", "extract-data", "top-accounts"]),
 ))]
+#[clap(group(ArgGroup::new("top-reports").multiple(true).args(&["size-report", "wallets", "compressibility"])))]
 struct Args {
-    #[clap(help = "Snapshot source (unpacked snapshot, archive file, or HTTP link)")]
+    #[clap(
+        help = "Snapshot source (unpacked snapshot, archive file, - for stdin, HTTP link, or S3 URL)"
+    )]
     source: String,
+    #[clap(
+        long,
+        action,
+        help = "Hide progress bars (default if stderr is not a terminal)"
+    )]
+    quiet: bool,
+    #[clap(
+        long,
+        action,
+        help = "On Ctrl-C, stop after the current AppendVec and finish the output (press again to abort)"
+    )]
+    checkpoint_on_interrupt: bool,
+    #[clap(
+        long,
+        value_name = "HOST:PORT",
+        help = "Serve Prometheus metrics over HTTP (requires the metrics feature)"
+    )]
+    metrics_addr: Option<String>,
+    #[clap(long, value_enum, default_value = "text", help = "Log line format")]
+    log_format: LogFormat,
+    #[clap(
+        long,
+        default_value_t = 30,
+        help = "HTTP connect and read timeout in seconds"
+    )]
+    http_timeout: u64,
+    #[clap(
+        long,
+        default_value_t = 3,
+        help = "Number of retries for failed HTTP requests"
+    )]
+    http_retries: u32,
+    #[clap(
+        long = "http-header",
+        value_parser = parse_http_header,
+        help = "Extra HTTP header (\"Key: Value\"), can be repeated"
+    )]
+    http_headers: Vec<(String, String)>,
+    #[clap(
+        long,
+        value_name = "URL",
+        help = "HTTP or SOCKS5 proxy for downloads, overriding HTTP_PROXY, HTTPS_PROXY, and ALL_PROXY"
+    )]
+    proxy: Option<String>,
     #[clap(long, action, help = "Write CSV to stdout")]
     csv: bool,
-    #[clap(long, help = "Export to new SQLite3 DB at this path")]
+    #[clap(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u8).range(1..=3),
+        help = "CSV schema version (see README)"
+    )]
+    csv_schema: u8,
+    #[clap(
+        long,
+        action,
+        requires = "csv",
+        conflicts_with = "csv-schema",
+        help = "Same as --csv-schema 2"
+    )]
+    csv_v2: bool,
+    #[clap(
+        long,
+        default_value = ",",
+        value_parser = parse_csv_delimiter,
+        requires = "csv",
+        help = "CSV field delimiter (single ASCII character, \\t for tab)"
+    )]
+    csv_delimiter: u8,
+    #[clap(
+        long,
+        default_value_t = 1 << 20,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Buffer size in bytes for CSV, program, and binary record outputs"
+    )]
+    buffer_size: u64,
+    #[clap(long, action, requires = "csv", help = "Omit the CSV header row")]
+    csv_no_header: bool,
+    #[clap(
+        long,
+        action,
+        requires = "csv",
+        help = "Write a '# slot=...' comment line with snapshot info before the CSV"
+    )]
+    csv_meta: bool,
+    #[clap(
+        long,
+        action,
+        requires = "csv",
+        help = "Append a CSV column classifying each account, e.g. token-account or programdata"
+    )]
+    classify: bool,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "base58",
+        help = "Pubkey encoding in CSV output (--csv and --diff-against)"
+    )]
+    encoding: PubkeyEncoding,
+    #[clap(
+        long,
+        action,
+        help = "Add the owner program's name to --csv, --size-report, --top-accounts, and --compressibility output"
+    )]
+    owner_summary: bool,
+    #[clap(
+        long,
+        requires = "owner-summary",
+        help = "JSON or TOML file mapping program IDs to names, extending the built-in list"
+    )]
+    program_names: Option<PathBuf>,
+    #[clap(
+        long,
+        help = "Export to new SQLite3 DB at this path, or :memory: to build it in memory"
+    )]
     sqlite_out: Option<String>,
-    #[clap(long, help = "SQLite3 cache size in MB")]
+    #[clap(
+        long,
+        help = "With --sqlite-out :memory:, copy the finished DB to this path"
+    )]
+    sqlite_backup: Option<PathBuf>,
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(i64).range(1..=1 << 20),
+        help = "Total SQLite3 cache size in MiB (1 to 1048576)"
+    )]
     sqlite_cache_size: Option<i64>,
+    #[clap(
+        long,
+        default_value_t = 50_000,
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Number of accounts inserted per SQLite3 transaction"
+    )]
+    sqlite_batch_size: u64,
+    #[clap(
+        long,
+        action,
+        help = "Use a write-ahead log for the SQLite3 DB (slower, crash-safe, readable during export)"
+    )]
+    sqlite_durable: bool,
+    #[clap(
+        long,
+        action,
+        requires = "sqlite-out",
+        help = "Keep the temp SQLite3 DB on failure, and continue from it on the next run"
+    )]
+    sqlite_resume: bool,
+    #[clap(
+        long,
+        requires = "sqlite-out",
+        help = "Directory for the temporary SQLite3 DB and sort files [default: next to the DB]"
+    )]
+    temp_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        action,
+        requires = "sqlite-out",
+        help = "Index transaction statuses from the status cache into the SQLite3 DB"
+    )]
+    status_cache: bool,
+    #[clap(
+        long = "metadata-program",
+        value_name = "PUBKEY",
+        value_parser = parse_pubkey,
+        help = "Index Token Metadata accounts owned by this program instead of the canonical one, can be repeated"
+    )]
+    metadata_programs: Vec<Pubkey>,
+    #[clap(
+        long,
+        action,
+        help = "Write a .sha256 checksum file next to the SQLite3 DB"
+    )]
+    checksum: bool,
     #[clap(long, action, help = "Index token program data")]
     tokens: bool,
-    #[clap(long, help = "Load Geyser plugin from given config file")]
-    geyser: Option<String>,
+    #[clap(
+        long,
+        help = "Load Geyser plugin from given config file, can be repeated"
+    )]
+    geyser: Vec<String>,
+    #[clap(
+        long,
+        alias = "geyser-compat",
+        default_value_t = 2,
+        value_parser = parse_geyser_account_version,
+        help = "ReplicaAccountInfo version sent to the Geyser plugin (1 or 2, also accepts v1 or v2)"
+    )]
+    geyser_account_version: u8,
+    #[clap(
+        long,
+        value_name = "BROKERS",
+        requires = "kafka-topic",
+        help = "Publish accounts to Kafka (requires the kafka feature)"
+    )]
+    kafka: Option<String>,
+    #[clap(long, help = "Kafka topic to publish accounts to")]
+    kafka_topic: Option<String>,
+    #[clap(
+        long,
+        default_value_t = 10_000,
+        help = "Maximum number of Kafka messages per batch"
+    )]
+    kafka_batch_size: u32,
+    #[clap(
+        long,
+        default_value = "lz4",
+        help = "Kafka compression codec (none, gzip, snappy, lz4, zstd)"
+    )]
+    kafka_compression: String,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Write each AppendVec as a <slot>.<id> file into this directory, without parsing accounts"
+    )]
+    append_vecs_out: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Write the data of each account to a <DIR>/<first byte>/<pubkey> file"
+    )]
+    extract_data: Option<PathBuf>,
+    #[clap(
+        long,
+        default_value_t = 1_000_000,
+        requires = "extract-data",
+        help = "Maximum number of files written by --extract-data"
+    )]
+    extract_data_limit: u64,
+    #[clap(
+        long,
+        help = "Write length-prefixed MessagePack account records (- for stdout)"
+    )]
+    msgpack_out: Option<String>,
+    #[clap(
+        long,
+        help = "Write length-prefixed CBOR account records (- for stdout)"
+    )]
+    cbor_out: Option<String>,
+    #[clap(
+        long,
+        help = "Write length-prefixed bincode account records, readable by this crate (- for stdout)"
+    )]
+    bincode_out: Option<String>,
+    #[clap(
+        long,
+        help = "Write accounts as an Arrow IPC stream (- for stdout, requires the arrow feature)"
+    )]
+    arrow_out: Option<String>,
+    #[clap(
+        long,
+        default_value_t = 65_536,
+        requires = "arrow-out",
+        help = "Number of accounts per --arrow-out record batch"
+    )]
+    arrow_batch_size: usize,
+    #[clap(
+        long,
+        help = "Write a bloom filter of all account pubkeys (- for stdout)"
+    )]
+    bloom_out: Option<String>,
+    #[clap(
+        long,
+        default_value_t = 0.01,
+        value_parser = parse_false_positive_rate,
+        help = "False positive rate of the --bloom-out filter"
+    )]
+    bloom_fpr: f64,
     #[clap(long, help = "Write programs tar stream")]
     programs_out: Option<String>,
+    #[clap(
+        long,
+        action,
+        requires = "programs-out",
+        help = "Name upgradeable programs by their program address instead of their ProgramData address"
+    )]
+    follow_programdata: bool,
+    #[clap(
+        long,
+        action,
+        requires = "follow-programdata",
+        help = "Also add a <programdata>.so symlink to each upgradeable program"
+    )]
+    keep_programdata_name: bool,
+    #[clap(
+        long,
+        action,
+        requires = "programs-out",
+        help = "Compress each program with zstd, using a dictionary trained on the first programs"
+    )]
+    programs_dict: bool,
+    #[clap(
+        long,
+        help = "Write eBPF opcode counts over all programs as CSV, like solana-opcode-stats"
+    )]
+    opcode_stats: Option<String>,
+    #[clap(
+        long,
+        action,
+        help = "Check that all accounts can be read, without writing output"
+    )]
+    check: bool,
+    #[clap(
+        long,
+        action,
+        help = "Count accounts and AppendVecs without reading account data"
+    )]
+    count_only: bool,
+    #[clap(
+        long,
+        value_name = "PUBKEY",
+        value_parser = parse_pubkey,
+        help = "Print the latest version of one account, exits nonzero if not found"
+    )]
+    inspect: Option<Pubkey>,
+    #[clap(
+        long,
+        help = "Write CSV of accounts that changed relative to this older snapshot source"
+    )]
+    diff_against: Option<String>,
+    #[clap(
+        long,
+        action,
+        help = "Recompute account hashes, the accounts delta hash, and the bank hash and compare them to the snapshot"
+    )]
+    verify_bank_hash: bool,
+    #[clap(
+        long,
+        default_value_t = 10,
+        help = "Number of account hash mismatches to log with --verify-bank-hash"
+    )]
+    max_mismatches: u64,
+    #[clap(
+        long,
+        action,
+        help = "Sum the lamports of all accounts and compare them to the bank's capitalization"
+    )]
+    verify_capitalization: bool,
+    #[clap(
+        long,
+        action,
+        help = "Print an order-independent digest of all account versions, to compare snapshots"
+    )]
+    fingerprint: bool,
+    #[clap(long, action, help = "Print account count and data size per owner")]
+    size_report: bool,
+    #[clap(long, action, help = "Print the number of accounts per rent epoch")]
+    rent_histogram: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Print the N largest accounts, by data size unless --top-accounts-by is set"
+    )]
+    top_accounts: Option<usize>,
+    #[clap(
+        long,
+        value_enum,
+        default_value = "data-len",
+        requires = "top-accounts",
+        help = "Field that --top-accounts ranks accounts by"
+    )]
+    top_accounts_by: TopAccountsKey,
+    #[clap(
+        long,
+        action,
+        help = "Print System Program accounts (wallets) sorted by SOL balance"
+    )]
+    wallets: bool,
+    #[clap(
+        long,
+        action,
+        help = "Print the zstd compression ratio of a sample of account data per owner"
+    )]
+    compressibility: bool,
+    #[clap(
+        long,
+        default_value_t = 0.01,
+        requires = "compressibility",
+        help = "Fraction of accounts (0 to 1) compressed by --compressibility"
+    )]
+    sample_rate: f64,
+    #[clap(
+        long,
+        requires = "top-reports",
+        help = "Only print the top N rows of --size-report, --wallets, or --compressibility"
+    )]
+    top: Option<usize>,
+    #[clap(
+        long,
+        action,
+        help = "Count pubkeys stored in more than one AppendVec version"
+    )]
+    report_duplicates: bool,
+    #[clap(
+        long,
+        action,
+        requires = "report-duplicates",
+        help = "Also list each version of duplicated pubkeys (memory intensive)"
+    )]
+    list_duplicates: bool,
+    #[clap(
+        long = "owner",
+        value_name = "PUBKEY",
+        value_parser = parse_pubkey,
+        help = "Only output accounts owned by this program, can be repeated"
+    )]
+    owners: Vec<Pubkey>,
+    #[clap(
+        long,
+        help = "Only output accounts owned by the programs listed in this file"
+    )]
+    owner_file: Option<PathBuf>,
+    #[clap(
+        long = "owner-not",
+        value_name = "PUBKEY",
+        value_parser = parse_pubkey,
+        help = "Drop accounts owned by this program, can be repeated"
+    )]
+    excluded_owners: Vec<Pubkey>,
+    #[clap(
+        long,
+        action,
+        help = "Fail on AppendVecs that are shorter than declared in the snapshot manifest"
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        default_value_t = MAX_PERMITTED_DATA_LENGTH,
+        help = "Treat accounts declaring more data than this many bytes as corrupt"
+    )]
+    max_account_data_len: u64,
+    #[clap(
+        long,
+        action,
+        help = "Log the account count, size, and processing time of each AppendVec"
+    )]
+    timings: bool,
+    #[clap(long, help = "Skip AppendVecs from slots before this one")]
+    min_slot: Option<u64>,
+    #[clap(long, help = "Skip AppendVecs from slots after this one")]
+    max_slot: Option<u64>,
+    #[clap(long, action, help = "Skip zero-lamport (deleted) accounts")]
+    skip_empty: bool,
+    #[clap(
+        long,
+        action,
+        conflicts_with = "skip-empty",
+        help = "Only output zero-lamport (deleted) accounts"
+    )]
+    only_empty: bool,
+    #[clap(long, help = "Skip accounts with less data than this many bytes")]
+    min_data_len: Option<u64>,
+    #[clap(long, help = "Skip accounts with more data than this many bytes")]
+    max_data_len: Option<u64>,
+    #[clap(long, action, help = "Only output executable accounts, i.e. programs")]
+    only_executable: bool,
+    #[clap(
+        long,
+        action,
+        conflicts_with = "only-executable",
+        help = "Skip executable accounts"
+    )]
+    exclude_executable: bool,
+    #[clap(
+        long,
+        help = "Decompress seekable zstd archives (default: 1) and verify account hashes (default: CPU count) on this many threads"
+    )]
+    num_threads: Option<usize>,
+}
+
+/// Actions that read the snapshot on their own, so they can't be combined with others.
+const STANDALONE_ACTIONS: &[&str] = &["--diff-against", "--verify-bank-hash", "--inspect"];
+
+impl Args {
+    /// Flags of the selected actions.
+    fn actions(&self) -> Vec<&'static str> {
+        [
+            ("--csv", self.csv),
+            ("--geyser", !self.geyser.is_empty()),
+            ("--sqlite-out", self.sqlite_out.is_some()),
+            ("--programs-out", self.programs_out.is_some()),
+            ("--opcode-stats", self.opcode_stats.is_some()),
+            ("--check", self.check),
+            ("--diff-against", self.diff_against.is_some()),
+            ("--size-report", self.size_report),
+            ("--rent-histogram", self.rent_histogram),
+            ("--top-accounts", self.top_accounts.is_some()),
+            ("--verify-bank-hash", self.verify_bank_hash),
+            ("--report-duplicates", self.report_duplicates),
+            ("--kafka", self.kafka.is_some()),
+            ("--msgpack-out", self.msgpack_out.is_some()),
+            ("--append-vecs-out", self.append_vecs_out.is_some()),
+            ("--extract-data", self.extract_data.is_some()),
+            ("--cbor-out", self.cbor_out.is_some()),
+            ("--bincode-out", self.bincode_out.is_some()),
+            ("--arrow-out", self.arrow_out.is_some()),
+            ("--bloom-out", self.bloom_out.is_some()),
+            ("--count-only", self.count_only),
+            ("--inspect", self.inspect.is_some()),
+            ("--wallets", self.wallets),
+            ("--compressibility", self.compressibility),
+            ("--verify-capitalization", self.verify_capitalization),
+            ("--fingerprint", self.fingerprint),
+        ]
+        .into_iter()
+        .filter(|(_, selected)| *selected)
+        .map(|(flag, _)| flag)
+        .collect()
+    }
+
+    /// Flags of the selected actions that write to stdout.
+    fn stdout_actions(&self) -> Vec<&'static str> {
+        let is_stdout = |path: &Option<String>| path.as_deref() == Some("-");
+        [
+            ("--csv", self.csv),
+            ("--size-report", self.size_report),
+            ("--rent-histogram", self.rent_histogram),
+            ("--top-accounts", self.top_accounts.is_some()),
+            ("--compressibility", self.compressibility),
+            ("--wallets", self.wallets),
+            ("--list-duplicates", self.list_duplicates),
+            ("--fingerprint", self.fingerprint),
+            ("--diff-against", self.diff_against.is_some()),
+            ("--inspect", self.inspect.is_some()),
+            ("--programs-out -", is_stdout(&self.programs_out)),
+            ("--opcode-stats -", is_stdout(&self.opcode_stats)),
+            ("--msgpack-out -", is_stdout(&self.msgpack_out)),
+            ("--cbor-out -", is_stdout(&self.cbor_out)),
+            ("--bincode-out -", is_stdout(&self.bincode_out)),
+            ("--arrow-out -", is_stdout(&self.arrow_out)),
+            ("--bloom-out -", is_stdout(&self.bloom_out)),
+        ]
+        .into_iter()
+        .filter(|(_, selected)| *selected)
+        .map(|(flag, _)| flag)
+        .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
 }
 
 fn main() {
-    env_logger::init_from_env(
+    let args = Args::parse();
+    init_logger(args.log_format, args.timings);
+    if let Err(e) = _main(args) {
+        error!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+    if interrupt::is_interrupted() {
+        std::process::exit(130);
+    }
+}
+
+fn init_logger(format: LogFormat, timings: bool) {
+    let mut builder = env_logger::Builder::from_env(
         env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
     );
-    if let Err(e) = _main() {
-        error!("{}", e);
-        std::process::exit(1);
+    if timings {
+        builder.filter_module(
+            concat!(module_path!(), "::timings"),
+            log::LevelFilter::Debug,
+        );
     }
+    if let LogFormat::Json = format {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{}", line)
+        });
+    }
+    builder.init();
 }
 
-fn _main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let mut loader = SupportedLoader::new(&args.source, Box::new(LoadProgressTracking {}))?;
-    if args.csv {
-        info!("Dumping to CSV");
-        let mut writer = CsvDumper::new();
+fn _main(args: Args) -> Result<(), CliError> {
+    if let Some(temp_dir) = &args.temp_dir {
+        // SQLite puts spill files of large sorts (e.g. index creation) here.
+        // Set before any thread is spawned, as setting the environment isn't thread-safe.
+        std::env::set_var("SQLITE_TMPDIR", temp_dir);
+    }
+    if args.num_threads == Some(0) {
+        return Err(CliError::config("--num-threads must be at least 1"));
+    }
+    let actions = args.actions();
+    if actions.len() > 1 {
+        if let Some(action) = actions.iter().find(|a| STANDALONE_ACTIONS.contains(a)) {
+            return Err(CliError::config(format!(
+                "{} can't be combined with other actions",
+                action
+            )));
+        }
+    }
+    let stdout_actions = args.stdout_actions();
+    if stdout_actions.len() > 1 {
+        return Err(CliError::config(format!(
+            "Only one action can write to stdout, got {}",
+            stdout_actions.join(", ")
+        )));
+    }
+    let http_options = HttpOptions {
+        timeout: Duration::from_secs(args.http_timeout),
+        retries: args.http_retries,
+        headers: args.http_headers,
+        proxy: args.proxy,
+    };
+    let mut owners: HashSet<Pubkey> = args.owners.iter().copied().collect();
+    if let Some(owner_file) = &args.owner_file {
+        owners.extend(read_pubkey_file(owner_file).map_err(CliError::config)?);
+    }
+    let filter = AccountFilter {
+        owners,
+        excluded_owners: args.excluded_owners.iter().copied().collect(),
+        min_data_len: args.min_data_len,
+        max_data_len: args.max_data_len,
+        executable: if args.only_executable {
+            Some(true)
+        } else if args.exclude_executable {
+            Some(false)
+        } else {
+            None
+        },
+        empty: if args.skip_empty {
+            EmptyFilter::Skip
+        } else if args.only_empty {
+            EmptyFilter::Only
+        } else {
+            EmptyFilter::Include
+        },
+    };
+    for (flag, data_len) in [
+        ("--min-data-len", args.min_data_len),
+        ("--max-data-len", args.max_data_len),
+    ] {
+        if data_len.is_some_and(|len| len > MAX_PERMITTED_DATA_LENGTH) {
+            return Err(CliError::config(format!(
+                "{} exceeds the maximum account size of {} bytes",
+                flag, MAX_PERMITTED_DATA_LENGTH
+            )));
+        }
+    }
+    if let (Some(min), Some(max)) = (args.min_data_len, args.max_data_len) {
+        if min > max {
+            return Err(CliError::config(
+                "--min-data-len must not be greater than --max-data-len",
+            ));
+        }
+    }
+    if !(0.0..=1.0).contains(&args.sample_rate) {
+        return Err(CliError::config("--sample-rate must be between 0 and 1"));
+    }
+    let program_names = if args.owner_summary {
+        let mut program_names = ProgramNames::default();
+        if let Some(path) = &args.program_names {
+            program_names.load_file(path).map_err(CliError::config)?;
+        }
+        Some(program_names)
+    } else {
+        None
+    };
+    if let Some(temp_dir) = &args.temp_dir {
+        check_writable_dir(temp_dir).map_err(CliError::config)?;
+    }
+    let slot_range = args.min_slot.unwrap_or(0)..=args.max_slot.unwrap_or(u64::MAX);
+    if slot_range.is_empty() {
+        return Err(CliError::config(
+            "--min-slot must not be greater than --max-slot",
+        ));
+    }
+    if args.arrow_batch_size == 0 {
+        return Err(CliError::config("--arrow-batch-size must be at least 1"));
+    }
+    if args.checkpoint_on_interrupt {
+        interrupt::install_handler();
+    }
+    let metrics: Option<Arc<Metrics>> = match &args.metrics_addr {
+        None => None,
+        #[cfg(feature = "metrics")]
+        Some(addr) => {
+            let metrics = Metrics::new();
+            metrics.serve(addr).map_err(CliError::config)?;
+            Some(metrics)
+        }
+        #[cfg(not(feature = "metrics"))]
+        Some(_) => {
+            return Err(CliError::config(
+                "Metrics support is not enabled, rebuild with the metrics feature",
+            ))
+        }
+    };
+    // Progress bars are drawn to stderr, so hide them if nobody is watching.
+    let quiet = args.quiet || !std::io::stderr().is_terminal();
+    let multi_progress = if quiet {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    } else {
+        MultiProgress::new()
+    };
+    let extractor_builder = || {
+        let progress_tracking: Box<dyn ReadProgressTracking> = if quiet {
+            Box::new(NullReadProgressTracking {})
+        } else {
+            Box::new(LoadProgressTracking {})
+        };
+        ExtractorBuilder::new()
+            .progress_tracking(progress_tracking)
+            .http_options(http_options.clone())
+            .slot_range(slot_range.clone())
+            .strict(args.strict)
+            .max_data_len(args.max_account_data_len)
+            .num_threads(args.num_threads.unwrap_or(1))
+    };
+    let source_loader = extractor_builder()
+        .open(&args.source)
+        .map_err(CliError::load)?;
+    let mut loader = ProgressLoader::new(source_loader, &multi_progress);
+    loader.set_timings(args.timings);
+    if let Some(metrics) = &metrics {
+        loader.register_metrics(metrics);
+    }
+    if let Some(base_source) = args.diff_against {
+        info!("Indexing base snapshot");
+        let base_source_loader = extractor_builder()
+            .open(&base_source)
+            .map_err(CliError::load)?;
+        let mut base_loader = ProgressLoader::new(base_source_loader, &multi_progress);
+        base_loader.set_timings(args.timings);
+        let mut old = AccountIndex::default();
+        for append_vec in base_loader.iter() {
+            old.on_append_vec(append_vec.map_err(CliError::parse)?)
+                .map_err(CliError::parse)?;
+        }
+        drop(base_loader);
+        info!("Indexed {} accounts in base snapshot", old.len());
+
+        info!("Indexing snapshot");
+        let mut new = AccountIndex::default();
         for append_vec in loader.iter() {
-            writer.dump_append_vec(append_vec?);
+            new.on_append_vec(append_vec.map_err(CliError::parse)?)
+                .map_err(CliError::parse)?;
         }
-        drop(writer);
-        println!("Done!");
+        info!("Indexed {} accounts in snapshot", new.len());
+
+        let stats = diff::write_diff(
+            old,
+            new,
+            args.encoding,
+            BufWriter::with_capacity(args.buffer_size as usize, stdout()),
+        )
+        .map_err(CliError::output)?;
+        info!(
+            "Done! {} added, {} removed, {} changed",
+            stats.added, stats.removed, stats.changed
+        );
+    }
+    if args.verify_bank_hash {
+        let bank = loader.bank().clone();
+        info!("Verifying bank hash of slot {}", bank.slot);
+        let mut verifier = BankHashVerifier::new(bank.slot, args.max_mismatches);
+        par_iter_append_vecs(
+            loader.iter(),
+            &mut verifier,
+            args.num_threads.unwrap_or_else(num_cpus::get),
+        )
+        .map_err(CliError::parse)?;
+        verifier.verify(&bank).map_err(CliError::parse)?;
+        info!("Bank hash verified");
     }
-    if let Some(geyser_config_path) = args.geyser {
-        info!("Dumping to Geyser plugin: {}", &geyser_config_path);
-        let plugin = unsafe { load_plugin(&geyser_config_path)? };
-        assert!(
-            plugin.account_data_notifications_enabled(),
-            "Geyser plugin does not accept account data notifications"
+    if let Some(pubkey) = args.inspect {
+        info!("Searching for account {}", pubkey);
+        let rent = loader.bank().rent;
+        let account = find_account(&mut loader, &pubkey)
+            .map_err(CliError::parse)?
+            .ok_or_else(|| CliError::parse(format!("Account {} not found", pubkey)))?;
+        inspect::write_account(stdout().lock(), &account, &rent).map_err(CliError::output)?;
+    }
+
+    // All other actions share a single pass over the snapshot.
+    let bank = loader.bank().clone();
+    let mut outputs: Vec<Box<dyn Output>> = Vec::new();
+    if args.csv {
+        info!("Dumping to CSV");
+        let writer = CsvDumper::new(
+            CsvOptions {
+                schema: if args.csv_v2 { 2 } else { args.csv_schema },
+                delimiter: args.csv_delimiter,
+                buffer_size: args.buffer_size as usize,
+                has_headers: !args.csv_no_header,
+                encoding: args.encoding,
+                program_names: program_names.clone(),
+                classify: args.classify,
+                meta_comment: args.csv_meta.then(|| {
+                    format!(
+                        "slot={} bank_hash={} capitalization={} produced_by=solana-snapshot-etl v{}",
+                        bank.slot,
+                        bank.hash,
+                        bank.capitalization,
+                        env!("CARGO_PKG_VERSION")
+                    )
+                }),
+            },
+            bank.rent,
+            &multi_progress,
         );
-        let mut dumper = GeyserDumper::new(plugin);
-        for append_vec in loader.iter() {
-            dumper.on_append_vec(append_vec?)?;
+        outputs.push(with_sink(writer, filter.clone()));
+    }
+    if !args.geyser.is_empty() {
+        #[cfg(feature = "geyser")]
+        {
+            let mut plugins = Vec::with_capacity(args.geyser.len());
+            for geyser_config_path in &args.geyser {
+                info!("Dumping to Geyser plugin: {}", geyser_config_path);
+                let plugin = unsafe { load_plugin(geyser_config_path).map_err(CliError::config)? };
+                if !plugin.account_data_notifications_enabled() {
+                    warn!(
+                        "Geyser plugin {} does not accept account data notifications, skipping",
+                        geyser_config_path
+                    );
+                    continue;
+                }
+                plugins.push(plugin);
+            }
+            if plugins.is_empty() {
+                return Err(CliError::config(
+                    "No Geyser plugin accepts account data notifications",
+                ));
+            }
+            let dumper = GeyserDumper::new(plugins, args.geyser_account_version, &multi_progress);
+            outputs.push(with_sink(dumper, filter.clone()));
         }
-        drop(dumper);
-        println!("Done!");
+        #[cfg(not(feature = "geyser"))]
+        return Err(CliError::config(
+            "Geyser support is not enabled, rebuild with the geyser feature",
+        ));
     }
     if let Some(sqlite_out_path) = args.sqlite_out {
-        info!("Dumping to SQLite3: {}", &sqlite_out_path);
-        let db_path = PathBuf::from(sqlite_out_path);
-        if db_path.exists() {
-            return Err("Refusing to overwrite database that already exists".into());
-        }
+        #[cfg(feature = "sqlite")]
+        {
+            info!("Dumping to SQLite3: {}", &sqlite_out_path);
+            let in_memory = sqlite_out_path == ":memory:";
+            let db_path = match &args.sqlite_backup {
+                Some(_) if !in_memory => {
+                    return Err(CliError::config(
+                        "--sqlite-backup requires --sqlite-out :memory:",
+                    ))
+                }
+                Some(backup_path) => Some(backup_path.clone()),
+                None if in_memory => None,
+                None => Some(PathBuf::from(sqlite_out_path)),
+            };
+            if db_path.as_ref().is_some_and(|path| path.exists()) {
+                // The DB gets written to a temp file and renamed, so it can't be streamed to a fifo.
+                return Err(CliError::output(
+                    "Refusing to overwrite database that already exists",
+                ));
+            }
+            if in_memory && args.sqlite_resume {
+                return Err(CliError::config(
+                    "--sqlite-resume requires a file for --sqlite-out",
+                ));
+            }
+            if in_memory && db_path.is_none() && args.checksum {
+                return Err(CliError::config(
+                    "--checksum requires --sqlite-backup with --sqlite-out :memory:",
+                ));
+            }
 
-        let mut indexer = SqliteIndexer::new(db_path)?;
-        if let Some(cache_size) = args.sqlite_cache_size {
-            indexer.set_cache_size(cache_size)?;
+            let mut indexer = if in_memory {
+                SqliteIndexer::new_in_memory(db_path, &bank, &multi_progress)
+                    .map_err(CliError::output)?
+            } else if args.sqlite_resume {
+                SqliteIndexer::new_resumable(
+                    db_path.unwrap(),
+                    args.temp_dir.as_deref(),
+                    &bank,
+                    &multi_progress,
+                )
+                .map_err(CliError::output)?
+            } else {
+                SqliteIndexer::new(
+                    db_path.unwrap(),
+                    args.temp_dir.as_deref(),
+                    &bank,
+                    &multi_progress,
+                )
+                .map_err(CliError::output)?
+            };
+            if let Some(cache_size) = args.sqlite_cache_size {
+                indexer
+                    .set_cache_size(cache_size)
+                    .map_err(CliError::output)?;
+            }
+            if args.sqlite_durable {
+                indexer.set_durable().map_err(CliError::output)?;
+            }
+            indexer.set_batch_size(args.sqlite_batch_size);
+            if !args.metadata_programs.is_empty() {
+                indexer.set_metadata_programs(args.metadata_programs);
+            }
+            indexer.set_checksum(args.checksum);
+            if let Some(metrics) = &metrics {
+                indexer.register_metrics(metrics);
+            }
+            if args.status_cache {
+                let entries = loader.status_cache().map_err(CliError::parse)?;
+                info!("Indexing {} status cache entries", entries.len());
+                indexer
+                    .insert_status_cache(&entries)
+                    .map_err(CliError::output)?;
+            }
+            outputs.push(with_sink(indexer, filter.clone()));
         }
-        let stats = indexer.insert_all(loader.iter())?;
-
-        info!("Done!");
-        info!("Dumped {} accounts", stats.accounts_total);
-        info!("Dumped {} token accounts", stats.token_accounts_total);
+        #[cfg(not(feature = "sqlite"))]
+        {
+            let _ = sqlite_out_path;
+            return Err(CliError::config(
+                "SQLite3 support is not enabled, rebuild with the sqlite feature",
+            ));
+        }
+    }
+    if let Some(brokers) = args.kafka {
+        #[cfg(feature = "kafka")]
+        {
+            use crate::kafka::{KafkaDumper, KafkaOptions};
+            info!("Publishing to Kafka: {}", &brokers);
+            let dumper = KafkaDumper::new(KafkaOptions {
+                brokers,
+                topic: args.kafka_topic.unwrap(),
+                batch_size: args.kafka_batch_size,
+                compression: args.kafka_compression,
+            })
+            .map_err(CliError::output)?;
+            outputs.push(with_sink(dumper, filter.clone()));
+        }
+        #[cfg(not(feature = "kafka"))]
+        {
+            let _ = brokers;
+            return Err(CliError::config(
+                "Kafka support is not enabled, rebuild with the kafka feature",
+            ));
+        }
+    }
+    if let Some(dir) = args.append_vecs_out {
+        info!("Writing AppendVecs to {}", dir.display());
+        outputs.push(with_finish(
+            AppendVecWriter::new(dir).map_err(CliError::output)?,
+            AppendVecWriter::finish,
+        ));
+    }
+    if let Some(dir) = args.extract_data {
+        info!(
+            "Writing account data to {} (at most {} files)",
+            dir.display(),
+            args.extract_data_limit
+        );
+        let writer = DataFileWriter::new(dir, args.extract_data_limit).map_err(CliError::output)?;
+        outputs.push(with_sink(writer, filter.clone()));
+    }
+    for (path, format) in [
+        (args.msgpack_out, BinaryFormat::MessagePack),
+        (args.cbor_out, BinaryFormat::Cbor),
+        (args.bincode_out, BinaryFormat::Bincode),
+    ] {
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+        info!("Writing {:?} account records to {}", format, &path);
+        let dumper = BinaryDumper::new(
+            open_writer(&path, args.buffer_size as usize).map_err(CliError::output)?,
+            format,
+        );
+        outputs.push(with_sink(dumper, filter.clone()));
+    }
+    if let Some(path) = args.arrow_out {
+        #[cfg(feature = "arrow")]
+        {
+            use crate::arrow::ArrowDumper;
+            info!("Writing Arrow IPC stream to {}", &path);
+            let dumper = ArrowDumper::new(
+                open_writer(&path, args.buffer_size as usize).map_err(CliError::output)?,
+                args.arrow_batch_size,
+            )
+            .map_err(CliError::output)?;
+            outputs.push(with_sink(dumper, filter.clone()));
+        }
+        #[cfg(not(feature = "arrow"))]
+        {
+            let _ = path;
+            return Err(CliError::config(
+                "Arrow support is not enabled, rebuild with the arrow feature",
+            ));
+        }
+    }
+    if let Some(bloom_path) = args.bloom_out {
+        let writer = BloomWriter::new(bank.append_vecs_len, args.bloom_fpr, filter.clone());
+        info!(
+            "Writing bloom filter ({} bytes, {} hashes) to {}",
+            writer.bloom().size_bytes(),
+            writer.bloom().num_hashes(),
+            &bloom_path
+        );
+        let output =
+            open_writer(&bloom_path, args.buffer_size as usize).map_err(CliError::output)?;
+        outputs.push(with_finish(writer, |writer| {
+            writer.bloom().write_to(output)?;
+            info!("Done writing bloom filter");
+            Ok(())
+        }));
     }
     if let Some(programs) = args.programs_out {
         info!("Dumping program accounts to {}", &programs);
-        let writer: Box<dyn Write> = if programs == "-" {
-            Box::new(stdout())
+        let mut dumper = ProgramDumper::new(
+            open_writer(&programs, args.buffer_size as usize).map_err(CliError::output)?,
+        );
+        if args.follow_programdata {
+            dumper.set_follow_programdata(args.keep_programdata_name);
+        }
+        if args.programs_dict {
+            dumper.set_dict_compression();
+        }
+        outputs.push(with_sink(dumper, filter.clone()));
+    }
+    if let Some(path) = args.opcode_stats {
+        #[cfg(feature = "rbpf")]
+        {
+            use crate::opcode_stats::OpcodeStatsCollector;
+            info!("Counting program opcodes to {}", &path);
+            let collector = OpcodeStatsCollector::new(
+                open_writer(&path, args.buffer_size as usize).map_err(CliError::output)?,
+            );
+            outputs.push(with_sink(collector, filter.clone()));
+        }
+        #[cfg(not(feature = "rbpf"))]
+        {
+            let _ = path;
+            return Err(CliError::config(
+                "Opcode stats are not enabled, rebuild with the rbpf feature",
+            ));
+        }
+    }
+    if args.size_report {
+        info!("Collecting account sizes per owner");
+        let report = SizeReport::new(filter.clone());
+        outputs.push(with_finish(report, |report| {
+            report.write_table(stdout(), args.top, program_names.as_ref())
+        }));
+    }
+    if args.rent_histogram {
+        info!("Counting accounts per rent epoch");
+        let histogram = RentHistogram::new(filter.clone());
+        outputs.push(with_finish(histogram, |histogram| {
+            histogram.write_table(stdout())
+        }));
+    }
+    if let Some(limit) = args.top_accounts {
+        info!("Collecting the {} largest accounts", limit);
+        let report = TopAccounts::new(limit, args.top_accounts_by, filter.clone());
+        outputs.push(with_finish(report, |report| {
+            report.write_table(stdout(), program_names.as_ref())
+        }));
+    }
+    if args.compressibility {
+        info!("Compressing account data sample");
+        let report = CompressibilityReport::new(filter.clone(), args.sample_rate)
+            .map_err(CliError::output)?;
+        outputs.push(with_finish(report, |report| {
+            report.write_table(stdout(), args.top, program_names.as_ref())
+        }));
+    }
+    if args.wallets {
+        info!("Collecting wallet balances");
+        outputs.push(with_finish(WalletReport::default(), |report| {
+            report.write_table(stdout(), args.top)
+        }));
+    }
+    if args.fingerprint {
+        info!("Fingerprinting accounts");
+        outputs.push(with_finish(
+            Fingerprint::new(filter.clone()),
+            |fingerprint| fingerprint.write_digest(stdout()),
+        ));
+    }
+    if args.report_duplicates {
+        info!("Counting duplicate pubkeys");
+        let report = if args.list_duplicates {
+            DuplicateReport::with_versions()
+        } else {
+            DuplicateReport::default()
+        };
+        outputs.push(with_finish(report, |mut report| {
+            report.log_summary();
+            report.write_versions(stdout())
+        }));
+    }
+    if args.verify_capitalization {
+        info!("Verifying capitalization of slot {}", bank.slot);
+        outputs.push(with_finish(CapitalizationVerifier::default(), |verifier| {
+            verifier.verify(&bank)?;
+            info!("Capitalization verified");
+            Ok(())
+        }));
+    }
+    if args.count_only {
+        info!("Counting accounts");
+        outputs.push(with_finish(AccountCounter::default(), |counter| {
+            info!(
+                "Counted {} accounts in {} AppendVecs",
+                counter.accounts_count, counter.append_vecs_count
+            );
+            Ok(())
+        }));
+    }
+    if args.check {
+        info!("Checking snapshot");
+        outputs.push(with_finish(SnapshotChecker::default(), |checker| {
+            info!(
+                "Checked {} accounts in {} AppendVecs",
+                checker.accounts_count, checker.append_vecs_count
+            );
+            info!(
+                "Slot {}: bank hash {}, accounts hash {}, accounts delta hash {}",
+                bank.slot, bank.hash, bank.accounts_hash, bank.accounts_delta_hash
+            );
+            Ok(())
+        }));
+    }
+    run_outputs(loader.iter(), outputs, metrics.as_deref())
+}
+
+/// Opens a buffered output file with [`open_output`], or stdout for `-`.
+fn open_writer(path: &str, buffer_size: usize) -> std::io::Result<Box<dyn Write>> {
+    let writer: Box<dyn Write> = if path == "-" {
+        Box::new(stdout())
+    } else {
+        Box::new(open_output(Path::new(path))?)
+    };
+    Ok(Box::new(BufWriter::with_capacity(buffer_size, writer)))
+}
+
+/// Creates a new output file, refusing to overwrite existing regular files.
+///
+/// Existing fifos and character devices are opened for writing,
+/// so output can be streamed into another process.
+fn open_output(path: &Path) -> std::io::Result<File> {
+    match std::fs::metadata(path) {
+        Ok(meta) if !meta.is_file() => OpenOptions::new().write(true).open(path),
+        _ => OpenOptions::new().write(true).create_new(true).open(path),
+    }
+}
+
+/// Fails early if temp files can't be created in the directory.
+fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    if !dir.is_dir() {
+        return Err(format!("{}: not a directory", dir.display()));
+    }
+    let probe = dir.join(format!(".solana-snapshot-etl-{}", std::process::id()));
+    File::create(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|e| format!("{}: not writable: {}", dir.display(), e))
+}
+
+/// Parses a `Key: Value` header argument.
+fn parse_http_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| "expected \"Key: Value\"".to_string())?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn parse_geyser_account_version(s: &str) -> Result<u8, String> {
+    match s.strip_prefix('v').unwrap_or(s) {
+        "1" => Ok(1),
+        "2" => Ok(2),
+        _ => Err("expected 1 or 2".to_string()),
+    }
+}
+
+fn parse_false_positive_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate < 1.0 => Ok(rate),
+        _ => Err("expected a number between 0 and 1 (exclusive)".to_string()),
+    }
+}
+
+fn parse_pubkey(s: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(s).map_err(|e| e.to_string())
+}
+
+fn parse_csv_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err("delimiter must be a single ASCII character".to_string()),
+    }
+}
+
+/// Shows how far the consumer got through the AppendVecs listed in the manifest.
+struct ProgressLoader {
+    loader: SupportedLoader,
+    progress_bar: ProgressBar,
+    timings: Option<Timings>,
+    stream_counters: Option<StreamCounters>,
+    append_vecs: Arc<AtomicU64>,
+}
+
+impl ProgressLoader {
+    const STREAM_STATS_INTERVAL: Duration = Duration::from_secs(30);
+
+    fn new(loader: SupportedLoader, multi_progress: &MultiProgress) -> Self {
+        let total_len = loader.bank().append_vecs_len;
+        let progress_bar = if total_len == 0 {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix:>10.bold.dim} {spinner:.green} {bytes}")
+                    .unwrap(),
+            )
         } else {
-            Box::new(
-                OpenOptions::new()
-                    .write(true)
-                    .create_new(true)
-                    .open(programs)?,
+            ProgressBar::new(total_len).with_style(
+                ProgressStyle::with_template(
+                    "{prefix:>10.bold.dim} {spinner:.green} [{bar:.cyan/blue}] {bytes}/{total_bytes} ({percent}%, ETA {eta})",
+                )
+                .unwrap()
+                .progress_chars("#>-"),
             )
         };
-        let mut dumper = ProgramDumper::new(writer);
-        for append_vec in loader.iter() {
-            dumper.on_append_vec(append_vec?)?;
+        progress_bar.set_prefix("appendvecs");
+        let stream_counters = loader.stream_counters();
+        Self {
+            stream_counters,
+            loader,
+            progress_bar: multi_progress.add(progress_bar),
+            timings: None,
+            append_vecs: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Logs the processing time of each AppendVec, and the slowest ones on drop.
+    fn set_timings(&mut self, timings: bool) {
+        self.timings = timings.then(Timings::default);
+    }
+
+    fn bank(&self) -> &BankSummary {
+        self.loader.bank()
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn status_cache(&mut self) -> solana_snapshot_etl::Result<Vec<StatusCacheEntry>> {
+        self.loader.status_cache()
+    }
+
+    /// Exports AppendVec and stream progress.
+    fn register_metrics(&self, metrics: &Metrics) {
+        let append_vecs = Arc::clone(&self.append_vecs);
+        metrics.register_counter(
+            "snapshot_etl_append_vecs_total",
+            "AppendVecs read from the snapshot.",
+            move || append_vecs.load(Ordering::Relaxed),
+        );
+        let progress_bar = self.progress_bar.clone();
+        metrics.register_counter(
+            "snapshot_etl_append_vec_bytes_total",
+            "Bytes of AppendVecs read from the snapshot.",
+            move || progress_bar.position(),
+        );
+        if let Some(stream_counters) = &self.stream_counters {
+            let counters = stream_counters.clone();
+            metrics.register_counter(
+                "snapshot_etl_compressed_bytes_total",
+                "Bytes read from the archive source.",
+                move || counters.stats().compressed_bytes,
+            );
+            let counters = stream_counters.clone();
+            metrics.register_counter(
+                "snapshot_etl_decompressed_bytes_total",
+                "Bytes produced by the archive decoder.",
+                move || counters.stats().decompressed_bytes,
+            );
         }
-        drop(dumper);
-        info!("Done!");
     }
-    Ok(())
+}
+
+impl SnapshotExtractor for ProgressLoader {
+    fn iter(&mut self) -> AppendVecIterator<'_> {
+        let progress_bar = &self.progress_bar;
+        let timings = &mut self.timings;
+        let stream_counters = &self.stream_counters;
+        let append_vecs = &self.append_vecs;
+        let mut last_stats_log = Instant::now();
+        let mut iter = self.loader.iter();
+        Box::new(std::iter::from_fn(move || {
+            // The consumer is done with the previous AppendVec once it asks for the next one.
+            if let Some(timings) = timings.as_mut() {
+                timings.finish();
+            }
+            if interrupt::is_interrupted() {
+                warn!("Interrupted, finishing output with the AppendVecs read so far");
+                return None;
+            }
+            let append_vec = iter.next()?;
+            if let Some(stream_counters) = stream_counters {
+                if last_stats_log.elapsed() >= Self::STREAM_STATS_INTERVAL {
+                    log_stream_stats(&stream_counters.stats());
+                    last_stats_log = Instant::now();
+                }
+            }
+            if let Ok(append_vec) = &append_vec {
+                append_vecs.fetch_add(1, Ordering::Relaxed);
+                progress_bar.inc(append_vec.len() as u64);
+                if let Some(timings) = timings.as_mut() {
+                    timings.start(append_vec);
+                }
+            }
+            Some(append_vec)
+        }))
+    }
+}
+
+impl Drop for ProgressLoader {
+    fn drop(&mut self) {
+        self.progress_bar.finish();
+        if let Some(stream_counters) = &self.stream_counters {
+            log_stream_stats(&stream_counters.stats());
+        }
+        if let Some(timings) = self.timings.as_mut() {
+            timings.log_summary();
+        }
+    }
+}
+
+fn log_stream_stats(stats: &StreamStats) {
+    info!(
+        "Read {} compressed, {} decompressed (ratio {:.2})",
+        HumanBytes(stats.compressed_bytes),
+        HumanBytes(stats.decompressed_bytes),
+        stats.ratio()
+    );
 }
 
 struct LoadProgressTracking {}
@@ -130,13 +1466,20 @@ impl ReadProgressTracking for LoadProgressTracking {
         rd: Box<dyn Read>,
         file_len: u64,
     ) -> Box<dyn Read> {
-        let progress_bar = ProgressBar::new(file_len).with_style(
-            ProgressStyle::with_template(
-                "{prefix:>10.bold.dim} {spinner:.green} [{bar:.cyan/blue}] {bytes}/{total_bytes} ({percent}%)",
+        let progress_bar = if file_len == 0 {
+            ProgressBar::new_spinner().with_style(
+                ProgressStyle::with_template("{prefix:>10.bold.dim} {spinner:.green} {bytes}")
+                    .unwrap(),
             )
-            .unwrap()
-            .progress_chars("#>-"),
-        );
+        } else {
+            ProgressBar::new(file_len).with_style(
+                ProgressStyle::with_template(
+                    "{prefix:>10.bold.dim} {spinner:.green} [{bar:.cyan/blue}] {bytes}/{total_bytes} ({percent}%)",
+                )
+                .unwrap()
+                .progress_chars("#>-"),
+            )
+        };
         progress_bar.set_prefix("manifest");
         Box::new(LoadProgressTracker {
             rd: progress_bar.wrap_read(rd),
@@ -173,52 +1516,3 @@ impl Read for LoadProgressTracker {
         self.rd.read_exact(buf)
     }
 }
-
-pub enum SupportedLoader {
-    Unpacked(UnpackedSnapshotExtractor),
-    ArchiveFile(ArchiveSnapshotExtractor<File>),
-    ArchiveDownload(ArchiveSnapshotExtractor<Response>),
-}
-
-impl SupportedLoader {
-    fn new(
-        source: &str,
-        progress_tracking: Box<dyn ReadProgressTracking>,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
-        if source.starts_with("http://") || source.starts_with("https://") {
-            Self::new_download(source)
-        } else {
-            Self::new_file(source.as_ref(), progress_tracking).map_err(Into::into)
-        }
-    }
-
-    fn new_download(url: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let resp = reqwest::blocking::get(url)?;
-        let loader = ArchiveSnapshotExtractor::from_reader(resp)?;
-        info!("Streaming snapshot from HTTP");
-        Ok(Self::ArchiveDownload(loader))
-    }
-
-    fn new_file(
-        path: &Path,
-        progress_tracking: Box<dyn ReadProgressTracking>,
-    ) -> solana_snapshot_etl::Result<Self> {
-        Ok(if path.is_dir() {
-            info!("Reading unpacked snapshot");
-            Self::Unpacked(UnpackedSnapshotExtractor::open(path, progress_tracking)?)
-        } else {
-            info!("Reading snapshot archive");
-            Self::ArchiveFile(ArchiveSnapshotExtractor::open(path)?)
-        })
-    }
-}
-
-impl SnapshotExtractor for SupportedLoader {
-    fn iter(&mut self) -> AppendVecIterator<'_> {
-        match self {
-            SupportedLoader::Unpacked(loader) => Box::new(loader.iter()),
-            SupportedLoader::ArchiveFile(loader) => Box::new(loader.iter()),
-            SupportedLoader::ArchiveDownload(loader) => Box::new(loader.iter()),
-        }
-    }
-}
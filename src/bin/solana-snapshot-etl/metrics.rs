@@ -0,0 +1,103 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+type Sampler = Box<dyn Fn() -> u64 + Send + Sync>;
+
+struct Metric {
+    name: &'static str,
+    help: &'static str,
+    kind: &'static str,
+    sample: Sampler,
+}
+
+/// Registry of counters exported in the Prometheus text format.
+///
+/// Metrics sample existing progress counters when scraped, so exporting them costs nothing per account.
+pub(crate) struct Metrics {
+    started: Instant,
+    metrics: Mutex<Vec<Metric>>,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            started: Instant::now(),
+            metrics: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Exports a monotonically increasing counter.
+    pub(crate) fn register_counter(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        sample: impl Fn() -> u64 + Send + Sync + 'static,
+    ) {
+        self.metrics.lock().unwrap().push(Metric {
+            name,
+            help,
+            kind: "counter",
+            sample: Box::new(sample),
+        });
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        for metric in self.metrics.lock().unwrap().iter() {
+            out += &format!(
+                "# HELP {name} {}\n# TYPE {name} {}\n{name} {}\n",
+                metric.help,
+                metric.kind,
+                (metric.sample)(),
+                name = metric.name
+            );
+        }
+        out += &format!(
+            "# HELP snapshot_etl_elapsed_seconds Time since the export started.\n\
+             # TYPE snapshot_etl_elapsed_seconds gauge\n\
+             snapshot_etl_elapsed_seconds {}\n",
+            self.started.elapsed().as_secs_f64()
+        );
+        out
+    }
+
+    /// Serves the metrics over HTTP on a background thread, answering every request.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn serve(self: &Arc<Self>, addr: &str) -> std::io::Result<()> {
+        use log::{info, warn};
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+        use std::time::Duration;
+
+        let listener = TcpListener::bind(addr)?;
+        info!(
+            "Serving metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+        let metrics = Arc::clone(self);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|mut stream| {
+                    // Only one kind of response, so the request itself is not parsed.
+                    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+                    let mut request = [0u8; 1024];
+                    let _ = stream.read(&mut request)?;
+                    let body = metrics.render();
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\n\
+                         Content-Type: text/plain; version=0.0.4\r\n\
+                         Content-Length: {}\r\n\
+                         Connection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                });
+                if let Err(e) = result {
+                    warn!("Failed to serve metrics: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -40,24 +40,42 @@ pub struct MetadataExtV1_2 {
 
 #[derive(BorshDeserialize)]
 pub struct Data {
-    pub name: String,
-    pub symbol: String,
-    pub uri: String,
+    pub name: LossyString,
+    pub symbol: LossyString,
+    pub uri: LossyString,
     pub seller_fee_basis_points: u16,
     pub creators: Option<Vec<Creator>>,
 }
 
+/// Instruction argument only, metadata accounts always store [`Data`]
+/// followed by the collection and uses in [`MetadataExtV1_2`].
 #[derive(BorshDeserialize)]
 pub struct DataV2 {
-    pub name: String,
-    pub symbol: String,
-    pub uri: String,
+    pub name: LossyString,
+    pub symbol: LossyString,
+    pub uri: LossyString,
     pub seller_fee_basis_points: u16,
     pub creators: Option<Vec<Creator>>,
     pub collection: Option<Collection>,
     pub uses: Option<Uses>,
 }
 
+/// Borsh string that replaces invalid UTF-8 with U+FFFD instead of failing.
+///
+/// Metadata strings are written by arbitrary clients, so they aren't guaranteed to be UTF-8.
+pub struct LossyString(pub String);
+
+impl BorshDeserialize for LossyString {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let bytes = Vec::<u8>::deserialize(buf)?;
+        let string = match String::from_utf8(bytes) {
+            Ok(string) => string,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        };
+        Ok(Self(string))
+    }
+}
+
 #[derive(BorshDeserialize)]
 pub struct Creator {
     pub address: Pubkey,
@@ -78,6 +96,29 @@ pub struct Uses {
     pub total: u64,
 }
 
+/// Delegates uses of an NFT.
+///
+/// The mint and the use authority are PDA seeds and are not stored.
+#[derive(BorshDeserialize)]
+pub struct UseAuthorityRecord {
+    pub allowed_uses: u64,
+    pub bump: u8,
+}
+
+/// Delegates verifying items of a collection.
+///
+/// The mint and the collection authority are PDA seeds and are not stored.
+/// Older records end after the bump.
+#[derive(BorshDeserialize)]
+pub struct CollectionAuthorityRecord {
+    pub bump: u8,
+}
+
+#[derive(BorshDeserialize)]
+pub struct CollectionAuthorityRecordExt {
+    pub update_authority: Option<Pubkey>,
+}
+
 #[derive(BorshDeserialize)]
 pub enum CollectionDetails {
     V1 { size: u64 },
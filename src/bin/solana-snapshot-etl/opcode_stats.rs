@@ -0,0 +1,76 @@
+use crate::output::AccountSink;
+use crate::programs::{parse_loader_account, LoaderAccount};
+use log::{info, warn};
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::opcode_stats::OpcodeStats;
+use solana_snapshot_etl::parallel::GenericResult;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::AddAssign;
+
+/// Counts the eBPF opcodes of all programs, like `solana-opcode-stats` on a `--programs-out` tarball.
+///
+/// Only the latest version of each program is counted, so the stats of a
+/// program are kept until the end of the snapshot.
+pub(crate) struct OpcodeStatsCollector {
+    writer: Box<dyn Write>,
+    /// Maps the address holding an ELF to (write version, opcode stats),
+    /// with no stats if the ELF can't be parsed.
+    programs: HashMap<Pubkey, (u64, Option<OpcodeStats>)>,
+}
+
+impl AccountSink for OpcodeStatsCollector {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        let elf = match parse_loader_account(account)? {
+            Some(LoaderAccount::Executable(elf)) | Some(LoaderAccount::ProgramData(elf)) => elf,
+            _ => return Ok(()),
+        };
+        let write_version = account.meta.write_version;
+        match self.programs.get(&account.meta.pubkey) {
+            Some((seen_version, _)) if *seen_version > write_version => return Ok(()),
+            _ => {}
+        }
+        let stats = match OpcodeStats::from_program(elf) {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                // E.g. ProgramData of a closed program, which has no ELF left.
+                warn!("Failed to parse program {}: {}", account.meta.pubkey, e);
+                None
+            }
+        };
+        self.programs
+            .insert(account.meta.pubkey, (write_version, stats));
+        Ok(())
+    }
+
+    fn finish(self) -> GenericResult<()> {
+        let mut total = OpcodeStats::default();
+        let mut num_programs = 0;
+        let mut num_skipped = 0;
+        for (_, stats) in self.programs.into_values() {
+            match stats {
+                Some(stats) => {
+                    total.add_assign(stats);
+                    num_programs += 1;
+                }
+                None => num_skipped += 1,
+            }
+        }
+        total.write_csv(self.writer)?;
+        info!(
+            "Done counting opcodes of {} programs, skipped {} unparsable ones",
+            num_programs, num_skipped
+        );
+        Ok(())
+    }
+}
+
+impl OpcodeStatsCollector {
+    pub(crate) fn new(writer: Box<dyn Write>) -> Self {
+        Self {
+            writer,
+            programs: HashMap::new(),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::error::CliError;
+use crate::filter::AccountFilter;
+use crate::metrics::Metrics;
+use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// A consumer fed by the shared pass over the snapshot, finalized once all AppendVecs were consumed.
+pub(crate) trait Output: AppendVecConsumer {
+    fn finish(self: Box<Self>) -> GenericResult<()>;
+}
+
+struct WithFinish<C, F> {
+    consumer: C,
+    finish: F,
+}
+
+impl<C, F> AppendVecConsumer for WithFinish<C, F>
+where
+    C: AppendVecConsumer,
+{
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        self.consumer.on_append_vec(append_vec)
+    }
+}
+
+impl<C, F> Output for WithFinish<C, F>
+where
+    C: AppendVecConsumer,
+    F: FnOnce(C) -> GenericResult<()>,
+{
+    fn finish(self: Box<Self>) -> GenericResult<()> {
+        (self.finish)(self.consumer)
+    }
+}
+
+/// Turns a consumer into an [`Output`], running `finish` on it after the pass.
+pub(crate) fn with_finish<'a, C, F>(consumer: C, finish: F) -> Box<dyn Output + 'a>
+where
+    C: AppendVecConsumer + 'a,
+    F: FnOnce(C) -> GenericResult<()> + 'a,
+{
+    Box::new(WithFinish { consumer, finish })
+}
+
+/// A destination for accounts, e.g. a file format or database.
+///
+/// Sinks only see accounts matching the filter passed to [`with_sink`],
+/// which also takes care of iterating over AppendVecs.
+pub(crate) trait AccountSink {
+    /// Called before the accounts of each AppendVec, e.g. to track the slot.
+    fn begin_append_vec(&mut self, _append_vec: &AppendVec) -> GenericResult<()> {
+        Ok(())
+    }
+
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()>;
+
+    /// Flushes the sink after the last account.
+    fn finish(self) -> GenericResult<()>;
+}
+
+struct SinkConsumer<S> {
+    sink: S,
+    filter: AccountFilter,
+}
+
+impl<S> AppendVecConsumer for SinkConsumer<S>
+where
+    S: AccountSink,
+{
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        self.sink.begin_append_vec(&append_vec)?;
+        for account in append_vec_accounts(&append_vec) {
+            if self.filter.matches(&account) {
+                self.sink.write(&account)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Output for SinkConsumer<S>
+where
+    S: AccountSink,
+{
+    fn finish(self: Box<Self>) -> GenericResult<()> {
+        self.sink.finish()
+    }
+}
+
+/// Turns a sink into an [`Output`], writing the accounts matching `filter`.
+pub(crate) fn with_sink<'a, S>(sink: S, filter: AccountFilter) -> Box<dyn Output + 'a>
+where
+    S: AccountSink + 'a,
+{
+    Box::new(SinkConsumer { sink, filter })
+}
+
+/// Hands each AppendVec to all outputs, then finishes them in order.
+///
+/// Outputs share the AppendVec's memory, so fanning out doesn't copy account data.
+/// With `metrics`, the accounts read are exported whichever outputs are enabled.
+pub(crate) fn run_outputs<I>(
+    append_vecs: I,
+    mut outputs: Vec<Box<dyn Output + '_>>,
+    metrics: Option<&Metrics>,
+) -> Result<(), CliError>
+where
+    I: IntoIterator<Item = solana_snapshot_etl::Result<AppendVec>>,
+{
+    let accounts = Arc::new(AtomicU64::new(0));
+    if let Some(metrics) = metrics {
+        let accounts = Arc::clone(&accounts);
+        metrics.register_counter(
+            "snapshot_etl_accounts_total",
+            "Accounts read from the snapshot, before filtering.",
+            move || accounts.load(Ordering::Relaxed),
+        );
+    }
+    if let Some((last, rest)) = outputs.split_last_mut() {
+        for append_vec in append_vecs {
+            let append_vec = append_vec.map_err(CliError::parse)?;
+            if metrics.is_some() {
+                let count = append_vec_accounts(&append_vec).count();
+                accounts.fetch_add(count as u64, Ordering::Relaxed);
+            }
+            for output in rest.iter_mut() {
+                output
+                    .on_append_vec(append_vec.clone())
+                    .map_err(CliError::output)?;
+            }
+            last.on_append_vec(append_vec).map_err(CliError::output)?;
+        }
+    }
+    for output in outputs {
+        output.finish().map_err(CliError::output)?;
+    }
+    Ok(())
+}
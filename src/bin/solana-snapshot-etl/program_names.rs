@@ -0,0 +1,98 @@
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Well-known owner programs.
+const KNOWN_PROGRAMS: &[(&str, &str)] = &[
+    ("11111111111111111111111111111111", "System Program"),
+    ("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA", "SPL Token"),
+    (
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        "SPL Token-2022",
+    ),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        "SPL Associated Token Account",
+    ),
+    (
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+        "Metaplex Token Metadata",
+    ),
+    (
+        "Stake11111111111111111111111111111111111111",
+        "Stake Program",
+    ),
+    (
+        "Vote111111111111111111111111111111111111111",
+        "Vote Program",
+    ),
+    (
+        "Config1111111111111111111111111111111111111",
+        "Config Program",
+    ),
+    ("Sysvar1111111111111111111111111111111111111", "Sysvar"),
+    (
+        "NativeLoader1111111111111111111111111111111",
+        "Native Loader",
+    ),
+    (
+        "BPFLoader1111111111111111111111111111111111",
+        "BPF Loader (deprecated)",
+    ),
+    ("BPFLoader2111111111111111111111111111111111", "BPF Loader"),
+    (
+        "BPFLoaderUpgradeab1e11111111111111111111111",
+        "BPF Upgradeable Loader",
+    ),
+    (
+        "AddressLookupTab1e1111111111111111111111111",
+        "Address Lookup Table",
+    ),
+];
+
+/// Maps program IDs to human-readable names.
+#[derive(Clone)]
+pub(crate) struct ProgramNames {
+    names: HashMap<Pubkey, String>,
+}
+
+impl Default for ProgramNames {
+    fn default() -> Self {
+        let names = KNOWN_PROGRAMS
+            .iter()
+            .map(|(program, name)| (Pubkey::from_str(program).unwrap(), name.to_string()))
+            .collect();
+        Self { names }
+    }
+}
+
+impl ProgramNames {
+    /// Adds names from a file mapping base58 program IDs to names, overriding built-in names.
+    ///
+    /// Files ending in `.toml` are read as TOML, anything else as JSON.
+    pub(crate) fn load_file(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let names: HashMap<String, String> = if path.extension() == Some("toml".as_ref()) {
+            toml::from_str(&content)?
+        } else {
+            serde_json::from_str(&content)?
+        };
+        for (program, name) in names {
+            let program = Pubkey::from_str(&program)
+                .map_err(|e| format!("{}: invalid pubkey {:?}: {}", path.display(), program, e))?;
+            self.names.insert(program, name);
+        }
+        Ok(())
+    }
+
+    /// Returns the name of a program, or its base58 ID if it is unknown.
+    pub(crate) fn name(&self, program: &Pubkey) -> String {
+        match self.names.get(program) {
+            Some(name) => name.clone(),
+            None => program.to_string(),
+        }
+    }
+}
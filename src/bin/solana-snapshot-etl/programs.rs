@@ -1,59 +1,296 @@
+use crate::output::AccountSink;
 use bincode::Options;
+use log::{info, warn};
 use solana_program::bpf_loader_upgradeable::UpgradeableLoaderState;
 use solana_program::pubkey::Pubkey;
 use solana_program::{bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable};
-use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
-use solana_snapshot_etl::append_vec_iter;
-use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use solana_snapshot_etl::append_vec::StoredAccountMeta;
+use solana_snapshot_etl::parallel::GenericResult;
+use std::collections::HashMap;
 use std::io::Write;
-use std::rc::Rc;
-use tar::{Builder, Header};
+use tar::{Builder, EntryType, Header};
+use zstd::bulk::Compressor;
+
+/// Number of programs the zstd dictionary is trained on.
+const DICT_SAMPLE_PROGRAMS: usize = 256;
+/// Maximum dictionary size, zstd's default.
+const DICT_SIZE: usize = 110 * 1024;
+const ZSTD_LEVEL: i32 = 9;
+/// Name of the archive entry holding the trained dictionary.
+const DICT_ENTRY_NAME: &str = "dictionary.zstd";
+
+/// Account of one of the BPF loaders.
+pub(crate) enum LoaderAccount<'a> {
+    /// Program of the non-upgradeable loaders, holding its ELF.
+    Executable(&'a [u8]),
+    /// ELF of an upgradeable program, stored at the ProgramData address.
+    ProgramData(&'a [u8]),
+    /// Upgradeable program, pointing to its ProgramData.
+    Program { programdata_address: Pubkey },
+}
+
+/// Decodes accounts owned by the BPF loaders, returning `None` for other accounts.
+pub(crate) fn parse_loader_account<'a>(
+    account: &StoredAccountMeta<'a>,
+) -> GenericResult<Option<LoaderAccount<'a>>> {
+    let owner = &account.account_meta.owner;
+    if bpf_loader_deprecated::check_id(owner) || bpf_loader::check_id(owner) {
+        if account.account_meta.executable {
+            return Ok(Some(LoaderAccount::Executable(account.data)));
+        }
+    } else if bpf_loader_upgradeable::check_id(owner) {
+        let header: UpgradeableLoaderState = bincode::options()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize(account.data)?;
+        match header {
+            UpgradeableLoaderState::ProgramData { .. } => {
+                return Ok(Some(LoaderAccount::ProgramData(&account.data[45..])));
+            }
+            UpgradeableLoaderState::Program {
+                programdata_address,
+            } => {
+                return Ok(Some(LoaderAccount::Program {
+                    programdata_address,
+                }));
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
 
 pub(crate) struct ProgramDumper {
     builder: Builder<Box<dyn Write>>,
+    follow_programdata: Option<ProgramDataResolver>,
+    dict_compression: Option<DictCompression>,
+}
+
+/// Compresses programs with a zstd dictionary trained on the first ones.
+enum DictCompression {
+    /// Holds back programs until there are enough to train the dictionary.
+    Sampling(Vec<(Pubkey, Vec<u8>)>),
+    Trained(Compressor<'static>),
+}
+
+/// Names upgradeable program ELFs by their program address instead of their ProgramData address.
+///
+/// A ProgramData account may come before or after the program that references it,
+/// so whichever is seen first waits for the other.
+#[derive(Default)]
+struct ProgramDataResolver {
+    /// Maps ProgramData address to program address, for programs whose ProgramData wasn't seen yet.
+    programs: HashMap<Pubkey, Pubkey>,
+    /// Maps ProgramData address to (write version, ELF) of ProgramData without a known program.
+    pending: HashMap<Pubkey, (u64, Vec<u8>)>,
+    /// Maps ProgramData address to program address, for ProgramData that was written.
+    resolved: HashMap<Pubkey, Pubkey>,
+    /// Also adds a `<programdata>.so` symlink to each program.
+    keep_programdata_name: bool,
 }
 
-impl AppendVecConsumer for ProgramDumper {
-    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
-        for account in append_vec_iter(Rc::new(append_vec)) {
-            self.insert_account(&account.access().unwrap())?;
+impl AccountSink for ProgramDumper {
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        match parse_loader_account(account)? {
+            Some(LoaderAccount::Executable(elf)) => {
+                self.write_executable(&account.meta.pubkey, elf)?;
+            }
+            Some(LoaderAccount::ProgramData(elf)) => {
+                if self.follow_programdata.is_some() {
+                    self.on_programdata(account, elf)?;
+                } else {
+                    self.write_executable(&account.meta.pubkey, elf)?;
+                }
+            }
+            Some(LoaderAccount::Program {
+                programdata_address,
+            }) if self.follow_programdata.is_some() => {
+                self.on_program(&account.meta.pubkey, &programdata_address)?;
+            }
+            _ => {}
         }
         Ok(())
     }
+
+    /// Writes the end-of-archive marker and flushes the output.
+    ///
+    /// ProgramData whose program was never seen is written under its own address.
+    fn finish(mut self) -> GenericResult<()> {
+        if let Some(resolver) = self.follow_programdata.take() {
+            info!(
+                "Resolved {} programs to their ProgramData",
+                resolver.resolved.len()
+            );
+            if !resolver.pending.is_empty() {
+                warn!(
+                    "{} ProgramData accounts have no program, writing them under their own address",
+                    resolver.pending.len()
+                );
+            }
+            for (programdata_address, (_, elf)) in resolver.pending {
+                self.write_executable(&programdata_address, &elf)?;
+            }
+        }
+        // Fewer programs than the sample size.
+        self.train_dict()?;
+        self.builder.into_inner()?.flush()?;
+        info!("Done dumping program accounts");
+        Ok(())
+    }
 }
 
 impl ProgramDumper {
     pub(crate) fn new(writer: Box<dyn Write>) -> Self {
         Self {
             builder: Builder::new(writer),
+            follow_programdata: None,
+            dict_compression: None,
         }
     }
 
-    pub(crate) fn insert_account(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
-        if bpf_loader_deprecated::check_id(&account.account_meta.owner)
-            || bpf_loader::check_id(&account.account_meta.owner)
-        {
-            if account.account_meta.executable {
-                self.write_executable(&account.meta.pubkey, account.data)?;
+    /// Compresses each program with zstd, using a dictionary trained on the first programs.
+    ///
+    /// Programs are written as `<address>.so.zst`, and the dictionary as `dictionary.zstd`.
+    /// If training fails, programs are compressed without a dictionary.
+    pub(crate) fn set_dict_compression(&mut self) {
+        self.dict_compression = Some(DictCompression::Sampling(Vec::new()));
+    }
+
+    /// Names upgradeable programs by their program address, optionally keeping the
+    /// ProgramData address as a symlink.
+    ///
+    /// ProgramData accounts seen before their program are buffered in memory.
+    pub(crate) fn set_follow_programdata(&mut self, keep_programdata_name: bool) {
+        self.follow_programdata = Some(ProgramDataResolver {
+            keep_programdata_name,
+            ..Default::default()
+        });
+    }
+
+    fn on_programdata(&mut self, account: &StoredAccountMeta, elf: &[u8]) -> GenericResult<()> {
+        let resolver = self.follow_programdata.as_mut().unwrap();
+        let programdata_address = account.meta.pubkey;
+        let program = resolver
+            .programs
+            .remove(&programdata_address)
+            .or_else(|| resolver.resolved.get(&programdata_address).copied());
+        match program {
+            Some(program) => self.write_program(&program, &programdata_address, elf),
+            None => {
+                let write_version = account.meta.write_version;
+                match resolver.pending.get(&programdata_address) {
+                    Some((pending_version, _)) if *pending_version > write_version => {}
+                    _ => {
+                        resolver
+                            .pending
+                            .insert(programdata_address, (write_version, elf.to_vec()));
+                    }
+                }
+                Ok(())
             }
-        } else if bpf_loader_upgradeable::check_id(&account.account_meta.owner) {
-            let header: UpgradeableLoaderState = bincode::options()
-                .with_fixint_encoding()
-                .allow_trailing_bytes()
-                .deserialize(account.data)?;
-            match header {
-                UpgradeableLoaderState::ProgramData { .. } => {
-                    self.write_executable(&account.meta.pubkey, &account.data[45..])?;
+        }
+    }
+
+    fn on_program(&mut self, program: &Pubkey, programdata_address: &Pubkey) -> GenericResult<()> {
+        let resolver = self.follow_programdata.as_mut().unwrap();
+        match resolver.pending.remove(programdata_address) {
+            Some((_, elf)) => self.write_program(program, programdata_address, &elf),
+            None => {
+                if !resolver.resolved.contains_key(programdata_address) {
+                    resolver.programs.insert(*programdata_address, *program);
                 }
-                _ => {}
+                Ok(())
             }
         }
+    }
+
+    fn write_program(
+        &mut self,
+        program: &Pubkey,
+        programdata_address: &Pubkey,
+        elf: &[u8],
+    ) -> GenericResult<()> {
+        self.write_executable(program, elf)?;
+        let resolver = self.follow_programdata.as_mut().unwrap();
+        let first_write = resolver
+            .resolved
+            .insert(*programdata_address, *program)
+            .is_none();
+        if resolver.keep_programdata_name && first_write {
+            let mut header = Header::new_ustar();
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+            let link_name = self.file_name(programdata_address);
+            let target = self.file_name(program);
+            self.builder.append_link(&mut header, link_name, target)?;
+        }
         Ok(())
     }
 
+    fn file_name(&self, address: &Pubkey) -> String {
+        match self.dict_compression {
+            Some(_) => format!("{}.so.zst", address),
+            None => format!("{}.so", address),
+        }
+    }
+
     fn write_executable(&mut self, address: &Pubkey, data: &[u8]) -> GenericResult<()> {
+        match &mut self.dict_compression {
+            None => self.append_file(&self.file_name(address), data),
+            Some(DictCompression::Sampling(samples)) => {
+                samples.push((*address, data.to_vec()));
+                if samples.len() >= DICT_SAMPLE_PROGRAMS {
+                    self.train_dict()?;
+                }
+                Ok(())
+            }
+            Some(DictCompression::Trained(compressor)) => {
+                let compressed = compressor.compress(data)?;
+                self.append_file(&self.file_name(address), &compressed)
+            }
+        }
+    }
+
+    /// Trains the dictionary on the held back programs, then writes them.
+    fn train_dict(&mut self) -> GenericResult<()> {
+        let samples = match self.dict_compression.take() {
+            Some(DictCompression::Sampling(samples)) => samples,
+            other => {
+                self.dict_compression = other;
+                return Ok(());
+            }
+        };
+        let elfs: Vec<&[u8]> = samples.iter().map(|(_, elf)| elf.as_slice()).collect();
+        let compressor = match zstd::dict::from_samples(&elfs, DICT_SIZE) {
+            Ok(dict) => {
+                info!(
+                    "Trained {} byte zstd dictionary on {} programs",
+                    dict.len(),
+                    samples.len()
+                );
+                self.append_file(DICT_ENTRY_NAME, &dict)?;
+                Compressor::with_dictionary(ZSTD_LEVEL, &dict)?
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to train zstd dictionary on {} programs, compressing without it: {}",
+                    samples.len(),
+                    e
+                );
+                Compressor::new(ZSTD_LEVEL)?
+            }
+        };
+        self.dict_compression = Some(DictCompression::Trained(compressor));
+        for (address, elf) in samples {
+            self.write_executable(&address, &elf)?;
+        }
+        Ok(())
+    }
+
+    fn append_file(&mut self, path: &str, data: &[u8]) -> GenericResult<()> {
         let mut header = Header::new_ustar();
-        header.set_path(format!("{}.so", address))?;
+        header.set_path(path)?;
         header.set_size(data.len() as u64);
         header.set_mode(0o644);
         header.set_cksum();
@@ -0,0 +1,50 @@
+use crate::filter::AccountFilter;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// `rent_epoch` of accounts that are exempt from rent collection.
+const RENT_EXEMPT_RENT_EPOCH: u64 = u64::MAX;
+
+/// Counts accounts per `rent_epoch`.
+pub(crate) struct RentHistogram {
+    epochs: BTreeMap<u64, u64>,
+    filter: AccountFilter,
+}
+
+impl AppendVecConsumer for RentHistogram {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if !self.filter.matches(&account) {
+                continue;
+            }
+            *self
+                .epochs
+                .entry(account.account_meta.rent_epoch)
+                .or_default() += 1;
+        }
+        Ok(())
+    }
+}
+
+impl RentHistogram {
+    pub(crate) fn new(filter: AccountFilter) -> Self {
+        Self {
+            epochs: BTreeMap::new(),
+            filter,
+        }
+    }
+
+    /// Prints the exempt sentinel first, then the other epochs in ascending order.
+    pub(crate) fn write_table<W: Write>(&self, mut writer: W) -> GenericResult<()> {
+        writeln!(writer, "{:<20} {:>12}", "rent_epoch", "accounts")?;
+        let exempt = self.epochs.get(&RENT_EXEMPT_RENT_EPOCH).copied();
+        writeln!(writer, "{:<20} {:>12}", "exempt", exempt.unwrap_or(0))?;
+        for (epoch, count) in self.epochs.range(..RENT_EXEMPT_RENT_EPOCH) {
+            writeln!(writer, "{:<20} {:>12}", epoch, count)?;
+        }
+        Ok(())
+    }
+}
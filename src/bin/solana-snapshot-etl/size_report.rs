@@ -0,0 +1,76 @@
+use crate::filter::AccountFilter;
+use crate::program_names::ProgramNames;
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Accumulates account data sizes per owner.
+pub(crate) struct SizeReport {
+    /// Maps owner to (account count, total data bytes).
+    owners: HashMap<Pubkey, (u64, u64)>,
+    filter: AccountFilter,
+}
+
+impl AppendVecConsumer for SizeReport {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if !self.filter.matches(&account) {
+                continue;
+            }
+            let entry = self.owners.entry(account.account_meta.owner).or_default();
+            entry.0 += 1;
+            entry.1 += account.meta.data_len;
+        }
+        Ok(())
+    }
+}
+
+impl SizeReport {
+    pub(crate) fn new(filter: AccountFilter) -> Self {
+        Self {
+            owners: HashMap::new(),
+            filter,
+        }
+    }
+
+    /// Prints owners sorted by total data size, largest first.
+    ///
+    /// With `program_names`, a column with the owner program's name is appended.
+    pub(crate) fn write_table<W: Write>(
+        &self,
+        mut writer: W,
+        top: Option<usize>,
+        program_names: Option<&ProgramNames>,
+    ) -> GenericResult<()> {
+        let mut owners: Vec<_> = self.owners.iter().collect();
+        owners.sort_unstable_by_key(|(_, (_, total_bytes))| Reverse(*total_bytes));
+        write!(
+            writer,
+            "{:<44} {:>12} {:>16} {:>12}",
+            "owner", "accounts", "total_bytes", "mean_bytes"
+        )?;
+        if program_names.is_some() {
+            write!(writer, "  program")?;
+        }
+        writeln!(writer)?;
+        for (owner, (count, total_bytes)) in owners.into_iter().take(top.unwrap_or(usize::MAX)) {
+            write!(
+                writer,
+                "{:<44} {:>12} {:>16} {:>12}",
+                owner.to_string(),
+                count,
+                total_bytes,
+                total_bytes / count
+            )?;
+            if let Some(program_names) = program_names {
+                write!(writer, "  {}", program_names.name(owner))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
@@ -1,57 +1,220 @@
 use borsh::BorshDeserialize;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use log::{error, warn};
-use rusqlite::{params, Connection};
+use log::{error, info, warn};
+use rusqlite::{params, Connection, DatabaseName};
+use solana_sdk::hash::Hasher;
 use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
 use solana_snapshot_etl::append_vec::{AppendVec, StoredAccountMeta};
-use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
-use solana_snapshot_etl::{append_vec_iter, AppendVecIterator};
+use solana_snapshot_etl::classify::{classify, AccountClass};
+use solana_snapshot_etl::parallel::GenericResult;
+use solana_snapshot_etl::status_cache::StatusCacheEntry;
+use solana_snapshot_etl::{is_rent_exempt, BankSummary};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::interrupt;
+use crate::metrics::Metrics;
 use crate::mpl_metadata;
+use crate::output::AccountSink;
 
 pub(crate) type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Maximum byte lengths of Metaplex metadata strings, the widths of their `token_metadata` columns.
+const MAX_NAME_LEN: usize = 32;
+const MAX_SYMBOL_LEN: usize = 10;
+const MAX_URI_LEN: usize = 200;
+
+/// Version of the table layout, bump on every schema change.
+const SCHEMA_VERSION: i64 = 7;
+
+const TABLES: &[&str] = &[
+    "account",
+    "token_mint",
+    "token_account",
+    "token_multisig",
+    "token_metadata",
+    "token_mint_balance",
+    "owner_stats",
+    "use_authority_record",
+    "collection_authority_record",
+    "status_cache",
+];
+
 pub(crate) struct SqliteIndexer {
     db: Connection,
-    db_path: PathBuf,
-    db_temp_guard: TempFileGuard,
+    destination: Destination,
+    checksum: bool,
+    batch_size: u64,
+    rent: Rent,
+    durable: bool,
+    metadata_programs: Vec<Pubkey>,
+    /// Accounts inserted in the open transaction, if any.
+    batch_len: Option<u64>,
+    resume: Option<ResumeState>,
 
     multi_progress: MultiProgress,
     progress: Arc<Progress>,
 }
 
+/// Where the finished DB is written.
+enum Destination {
+    /// The DB is a temp file, which is moved to `path` on success.
+    File {
+        path: PathBuf,
+        temp_guard: TempFileGuard,
+    },
+    /// The DB is held in memory, and copied to `backup_path` on success if set.
+    Memory { backup_path: Option<PathBuf> },
+}
+
+/// Tracks which AppendVecs are fully indexed, so an interrupted export can be resumed.
+///
+/// Each AppendVec is recorded in `done_appendvecs` in the same transaction as its accounts.
+struct ResumeState {
+    /// AppendVecs indexed by earlier runs, as (slot, id).
+    done: HashSet<(u64, u64)>,
+    /// AppendVec whose accounts are being inserted.
+    current: Option<(u64, u64)>,
+    /// Whether the current AppendVec was indexed by an earlier run.
+    skip: bool,
+}
+
 struct Progress {
     accounts_counter: ProgressCounter,
     token_accounts_counter: ProgressCounter,
     metaplex_accounts_counter: ProgressCounter,
 }
 
-pub(crate) struct IndexStats {
-    pub(crate) accounts_total: u64,
-    pub(crate) token_accounts_total: u64,
+struct IndexStats {
+    accounts_total: u64,
+    token_accounts_total: u64,
 }
 
 impl SqliteIndexer {
-    pub(crate) fn new(db_path: PathBuf) -> Result<Self> {
+    /// Creates the DB as a temp file in `temp_dir` (next to `db_path` if `None`),
+    /// which is moved to `db_path` on success.
+    pub(crate) fn new(
+        db_path: PathBuf,
+        temp_dir: Option<&Path>,
+        bank: &BankSummary,
+        multi_progress: &MultiProgress,
+    ) -> Result<Self> {
         // Create temporary DB file, which gets promoted on success.
-        let temp_file_name = format!("_{}.tmp", db_path.file_name().unwrap().to_string_lossy());
-        let db_temp_path = db_path.with_file_name(&temp_file_name);
+        let db_temp_path = Self::temp_path(&db_path, temp_dir);
         let _ = std::fs::remove_file(&db_temp_path);
-        let db_temp_guard = TempFileGuard::new(db_temp_path.clone());
+        let temp_guard = TempFileGuard::new(db_temp_path.clone());
 
         // Open database.
-        let db = Self::create_db(&db_temp_path)?;
+        let db = Self::create_db(Connection::open(&db_temp_path)?, bank.slot)?;
+        let destination = Destination::File {
+            path: db_path,
+            temp_guard,
+        };
+        Ok(Self::with_db(db, destination, bank.rent, multi_progress))
+    }
 
+    /// Like [`Self::new`], but continues with the temp DB of an interrupted run if there is one.
+    ///
+    /// The temp DB is kept if the export fails, and uses a write-ahead log so it survives crashes.
+    /// AppendVecs that were fully indexed by earlier runs are skipped.
+    pub(crate) fn new_resumable(
+        db_path: PathBuf,
+        temp_dir: Option<&Path>,
+        bank: &BankSummary,
+        multi_progress: &MultiProgress,
+    ) -> Result<Self> {
+        let db_temp_path = Self::temp_path(&db_path, temp_dir);
+        let (db, done) = if db_temp_path.exists() {
+            let db = Connection::open(&db_temp_path)?;
+            let (schema_version, slot): (i64, i64) = db
+                .query_row("SELECT schema_version, slot FROM meta;", [], |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })
+                .map_err(|e| format!("{}: not a resumable DB: {}", db_temp_path.display(), e))?;
+            if schema_version != SCHEMA_VERSION || slot as u64 != bank.slot {
+                return Err(format!(
+                    "{} was created for slot {} with schema version {}, delete it to start over",
+                    db_temp_path.display(),
+                    slot,
+                    schema_version
+                )
+                .into());
+            }
+            let done = db
+                .prepare("SELECT slot, id FROM done_appendvecs;")?
+                .query_map([], |row| {
+                    Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+                })?
+                .collect::<rusqlite::Result<HashSet<_>>>()?;
+            info!(
+                "Resuming {}, skipping {} indexed AppendVecs",
+                db_temp_path.display(),
+                done.len()
+            );
+            // These are written once per run rather than per AppendVec.
+            db.execute_batch(
+                "DELETE FROM status_cache; DELETE FROM token_mint_balance; DELETE FROM owner_stats;",
+            )?;
+            (db, done)
+        } else {
+            let db = Self::create_db(Connection::open(&db_temp_path)?, bank.slot)?;
+            db.execute(
+                "\
+CREATE TABLE done_appendvecs (
+    slot INTEGER(8) NOT NULL,
+    id INTEGER(8) NOT NULL,
+    PRIMARY KEY (slot, id)
+);",
+                [],
+            )?;
+            (db, HashSet::new())
+        };
+        let destination = Destination::File {
+            path: db_path,
+            temp_guard: TempFileGuard::kept_on_error(db_temp_path),
+        };
+        let mut indexer = Self::with_db(db, destination, bank.rent, multi_progress);
+        indexer.set_durable()?;
+        indexer.resume = Some(ResumeState {
+            done,
+            current: None,
+            skip: false,
+        });
+        Ok(indexer)
+    }
+
+    /// Builds the DB in memory, then copies it to `backup_path` with SQLite's backup API.
+    ///
+    /// Without `backup_path`, the DB is discarded once the export finishes.
+    pub(crate) fn new_in_memory(
+        backup_path: Option<PathBuf>,
+        bank: &BankSummary,
+        multi_progress: &MultiProgress,
+    ) -> Result<Self> {
+        let db = Self::create_db(Connection::open_in_memory()?, bank.slot)?;
+        let destination = Destination::Memory { backup_path };
+        Ok(Self::with_db(db, destination, bank.rent, multi_progress))
+    }
+
+    fn with_db(
+        db: Connection,
+        destination: Destination,
+        rent: Rent,
+        multi_progress: &MultiProgress,
+    ) -> Self {
         // Create progress bars.
         let spinner_style = ProgressStyle::with_template(
             "{prefix:>13.bold.dim} {spinner} rate={per_sec:>13} total={human_pos:>11}",
         )
         .unwrap();
-        let multi_progress = MultiProgress::new();
+        let multi_progress = multi_progress.clone();
         let accounts_counter = ProgressCounter::new(
             multi_progress.add(
                 ProgressBar::new_spinner()
@@ -74,10 +237,16 @@ impl SqliteIndexer {
             ),
         );
 
-        Ok(Self {
+        Self {
             db,
-            db_path,
-            db_temp_guard,
+            destination,
+            checksum: false,
+            batch_size: 50_000,
+            rent,
+            durable: false,
+            metadata_programs: vec![mpl_metadata::id()],
+            batch_len: None,
+            resume: None,
 
             multi_progress,
             progress: Arc::new(Progress {
@@ -85,23 +254,54 @@ impl SqliteIndexer {
                 token_accounts_counter,
                 metaplex_accounts_counter,
             }),
-        })
+        }
+    }
+
+    /// Returns the path of the temp DB, `_<name>.tmp` in `temp_dir` or next to `db_path`.
+    fn temp_path(db_path: &Path, temp_dir: Option<&Path>) -> PathBuf {
+        let temp_file_name = format!("_{}.tmp", db_path.file_name().unwrap().to_string_lossy());
+        match temp_dir {
+            Some(temp_dir) => temp_dir.join(&temp_file_name),
+            None => db_path.with_file_name(&temp_file_name),
+        }
     }
 
-    fn create_db(path: &Path) -> Result<Connection> {
-        let db = Connection::open(&path)?;
+    fn create_db(db: Connection, slot: u64) -> Result<Connection> {
         db.pragma_update(None, "synchronous", false)?;
         db.pragma_update(None, "journal_mode", "off")?;
         db.pragma_update(None, "locking_mode", "exclusive")?;
         db.execute(
             "\
+CREATE TABLE meta (
+    schema_version INTEGER NOT NULL,
+    crate_version TEXT NOT NULL,
+    slot INTEGER(8) NOT NULL,
+    created_at INTEGER(8) NOT NULL,
+    complete INTEGER(1) NOT NULL
+);",
+            [],
+        )?;
+        let created_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        db.execute(
+            "INSERT INTO meta (schema_version, crate_version, slot, created_at, complete) VALUES (?, ?, ?, ?, ?);",
+            params![
+                SCHEMA_VERSION,
+                env!("CARGO_PKG_VERSION"),
+                slot as i64,
+                created_at as i64,
+                false,
+            ],
+        )?;
+        db.execute(
+            "\
 CREATE TABLE account  (
     pubkey BLOB(32) NOT NULL PRIMARY KEY,
     data_len INTEGER(8) NOT NULL,
     owner BLOB(32) NOT NULL,
     lamports INTEGER(8) NOT NULL,
     executable INTEGER(1) NOT NULL,
-    rent_epoch INTEGER(8) NOT NULL
+    rent_epoch INTEGER(8) NOT NULL,
+    rent_exempt INTEGER(1) NOT NULL
 );",
             [],
         )?;
@@ -156,70 +356,463 @@ CREATE TABLE token_metadata (
     is_mutable INTEGER(1) NOT NULL,
     edition_nonce INTEGER(2) NULL,
     collection_verified INTEGER(1) NULL,
-    collection_key BLOB(32) NULL
+    collection_key BLOB(32) NULL,
+    uses_method INTEGER(1) NULL,
+    uses_remaining INTEGER(8) NULL,
+    uses_total INTEGER(8) NULL
+);",
+            [],
+        )?;
+        db.execute(
+            "\
+CREATE TABLE token_mint_balance (
+    mint BLOB(32) NOT NULL PRIMARY KEY,
+    amount INTEGER(8) NOT NULL,
+    holders INTEGER(8) NOT NULL,
+    accounts INTEGER(8) NOT NULL
+);",
+            [],
+        )?;
+        db.execute(
+            "\
+CREATE TABLE owner_stats (
+    owner BLOB(32) NOT NULL PRIMARY KEY,
+    account_count INTEGER(8) NOT NULL,
+    total_lamports INTEGER(8) NOT NULL,
+    total_data_len INTEGER(8) NOT NULL
+);",
+            [],
+        )?;
+        db.execute(
+            "\
+CREATE TABLE use_authority_record (
+    pubkey BLOB(32) NOT NULL PRIMARY KEY,
+    allowed_uses INTEGER(8) NOT NULL,
+    bump INTEGER(1) NOT NULL
+);",
+            [],
+        )?;
+        db.execute(
+            "\
+CREATE TABLE collection_authority_record (
+    pubkey BLOB(32) NOT NULL PRIMARY KEY,
+    bump INTEGER(1) NOT NULL,
+    update_authority BLOB(32) NULL
+);",
+            [],
+        )?;
+        db.execute(
+            "\
+CREATE TABLE status_cache (
+    slot INTEGER(8) NOT NULL,
+    is_root INTEGER(1) NOT NULL,
+    blockhash BLOB(32) NOT NULL,
+    key_index INTEGER(2) NOT NULL,
+    key_slice BLOB(20) NOT NULL,
+    error TEXT NULL
 );",
             [],
         )?;
         Ok(db)
     }
 
+    /// Sets the page cache size in MiB.
+    ///
+    /// This is the total for the export, all inserts go through a single connection.
     pub(crate) fn set_cache_size(&mut self, size_mib: i64) -> Result<()> {
-        let size = size_mib * 1024;
-        self.db.pragma_update(None, "cache_size", -size)?;
+        if let Some(total_mib) = total_memory_mib() {
+            if size_mib as u64 > total_mib {
+                warn!(
+                    "SQLite3 cache size {} MiB exceeds total memory ({} MiB)",
+                    size_mib, total_mib
+                );
+            }
+        }
+        // Negative values are interpreted as KiB rather than pages.
+        self.db
+            .pragma_update(None, "cache_size", -(size_mib * 1024))?;
+        let page_size: i64 = self
+            .db
+            .pragma_query_value(None, "page_size", |row| row.get(0))?;
+        info!(
+            "SQLite3 cache size: {} MiB ({} pages of {} bytes)",
+            size_mib,
+            size_mib * 1024 * 1024 / page_size,
+            page_size
+        );
         Ok(())
     }
 
-    pub(crate) fn insert_all(mut self, iterator: AppendVecIterator) -> Result<IndexStats> {
-        let mut worker = Worker {
-            db: &self.db,
-            progress: Arc::clone(&self.progress),
-        };
-        for append_vec in iterator {
-            worker.on_append_vec(append_vec?)?;
+    /// Writes a `<db>.sha256` sidecar with the digest of the final DB file.
+    pub(crate) fn set_checksum(&mut self, checksum: bool) {
+        self.checksum = checksum;
+    }
+
+    /// Sets the number of accounts inserted per transaction.
+    pub(crate) fn set_batch_size(&mut self, batch_size: u64) {
+        self.batch_size = batch_size;
+    }
+
+    /// Exports the account counters.
+    pub(crate) fn register_metrics(&self, metrics: &Metrics) {
+        let progress = Arc::clone(&self.progress);
+        metrics.register_counter(
+            "snapshot_etl_sqlite_accounts_total",
+            "Accounts inserted into SQLite3.",
+            move || progress.accounts_counter.get(),
+        );
+        let progress = Arc::clone(&self.progress);
+        metrics.register_counter(
+            "snapshot_etl_sqlite_token_accounts_total",
+            "SPL Token accounts inserted into SQLite3.",
+            move || progress.token_accounts_counter.get(),
+        );
+    }
+
+    /// Sets the programs whose accounts are indexed into `token_metadata`,
+    /// replacing the canonical Token Metadata program.
+    pub(crate) fn set_metadata_programs(&mut self, programs: Vec<Pubkey>) {
+        self.metadata_programs = programs;
+    }
+
+    /// Switches to a write-ahead log, so the temp DB survives crashes
+    /// and can be queried by other processes while it is being written.
+    pub(crate) fn set_durable(&mut self) -> Result<()> {
+        if let Destination::Memory { .. } = self.destination {
+            return Err("An in-memory SQLite3 DB can't be durable".into());
+        }
+        self.db.pragma_update(None, "locking_mode", "normal")?;
+        self.db.pragma_update(None, "journal_mode", "wal")?;
+        self.db.pragma_update(None, "synchronous", "normal")?;
+        self.durable = true;
+        Ok(())
+    }
+
+    /// Inserts transaction statuses into `status_cache`.
+    pub(crate) fn insert_status_cache(&self, entries: &[StatusCacheEntry]) -> Result<()> {
+        self.db.execute_batch("BEGIN;")?;
+        let mut insert = self.db.prepare(
+            "INSERT INTO status_cache (slot, is_root, blockhash, key_index, key_slice, error) VALUES (?, ?, ?, ?, ?, ?);",
+        )?;
+        for entry in entries {
+            insert.execute(params![
+                entry.slot as i64,
+                entry.is_root,
+                entry.blockhash.as_ref(),
+                entry.key_index as i64,
+                &entry.key_slice[..],
+                entry.status.as_ref().err().map(|e| e.to_string()),
+            ])?;
+        }
+        self.db.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+
+    /// Commits the last batch, derives the remaining tables, and moves the DB into place.
+    fn finish_db(mut self) -> Result<IndexStats> {
+        self.record_done()?;
+        if self.batch_len.take().is_some() {
+            self.db.execute_batch("COMMIT;")?;
         }
-        self.db.pragma_update(None, "query_only", true)?;
         let stats = IndexStats {
             accounts_total: self.progress.accounts_counter.get(),
             token_accounts_total: self.progress.token_accounts_counter.get(),
         };
-        self.db_temp_guard.promote(self.db_path)?;
+        if self.resume.is_some() && interrupt::is_interrupted() {
+            // Leave the temp DB and its `done_appendvecs` for the next run to continue from.
+            return Ok(stats);
+        }
+        self.insert_mint_balances()?;
+        self.insert_owner_stats()?;
+        // A checkpointed run stops reading early, so its DB lacks the remaining accounts.
+        let complete = !interrupt::is_interrupted();
+        self.db
+            .execute("UPDATE meta SET complete = ?;", [complete])?;
+        if !complete {
+            warn!("Snapshot was not fully read, the DB is marked incomplete (meta.complete = 0)");
+        }
+        if self.resume.is_some() {
+            self.db.execute("DROP TABLE done_appendvecs;", [])?;
+        }
+        if self.durable {
+            // Fold the WAL back into the DB file, so it can be moved alone.
+            self.db.pragma_update(None, "journal_mode", "delete")?;
+        }
+        self.db.pragma_update(None, "query_only", true)?;
+        let row_counts = if self.checksum {
+            Some(self.count_rows()?)
+        } else {
+            None
+        };
+        let db_path = match &mut self.destination {
+            Destination::File { path, temp_guard } => {
+                temp_guard.promote(&*path)?;
+                Some(path)
+            }
+            Destination::Memory {
+                backup_path: Some(path),
+            } => {
+                info!("Backing up in-memory DB to {}", path.display());
+                if let Err(e) = self.db.backup(DatabaseName::Main, &*path, None) {
+                    let _ = std::fs::remove_file(&*path);
+                    return Err(e.into());
+                }
+                Some(path)
+            }
+            Destination::Memory { backup_path: None } => None,
+        };
+        if let (Some(db_path), Some(row_counts)) = (db_path, row_counts) {
+            write_checksum_file(db_path, &row_counts)?;
+        }
         let _ = &self.multi_progress;
         Ok(stats)
     }
+
+    /// Records the current AppendVec in `done_appendvecs`, in the transaction holding its accounts.
+    ///
+    /// When resuming, transactions are only committed here, between AppendVecs.
+    fn record_done(&mut self) -> Result<()> {
+        let (slot, id) = match self
+            .resume
+            .as_mut()
+            .and_then(|resume| resume.current.take())
+        {
+            Some(append_vec) => append_vec,
+            None => return Ok(()),
+        };
+        let batch_len = match self.batch_len {
+            Some(batch_len) => batch_len,
+            None => {
+                self.db.execute_batch("BEGIN;")?;
+                0
+            }
+        };
+        self.db
+            .prepare_cached("INSERT INTO done_appendvecs (slot, id) VALUES (?, ?);")?
+            .execute(params![slot as i64, id as i64])?;
+        if batch_len >= self.batch_size {
+            self.db.execute_batch("COMMIT;")?;
+            self.batch_len = None;
+        } else {
+            self.batch_len = Some(batch_len);
+        }
+        Ok(())
+    }
+
+    /// Sums token account balances and counts holders per mint.
+    ///
+    /// This reads back `token_account` rather than accumulating during inserts,
+    /// because accounts stored in multiple AppendVecs would otherwise be counted once per version.
+    fn insert_mint_balances(&self) -> Result<()> {
+        info!("Summing token balances per mint");
+        // Maps mint to (total amount, holders, accounts).
+        let mut mints = HashMap::<Pubkey, (u128, u64, u64)>::new();
+        let mut select = self.db.prepare("SELECT mint, amount FROM token_account;")?;
+        let mut rows = select.query([])?;
+        while let Some(row) = rows.next()? {
+            let mint: [u8; 32] = row.get(0)?;
+            let amount = row.get::<_, i64>(1)? as u64;
+            let entry = mints.entry(Pubkey::new_from_array(mint)).or_default();
+            entry.0 += amount as u128;
+            if amount > 0 {
+                entry.1 += 1;
+            }
+            entry.2 += 1;
+        }
+
+        self.db.execute_batch("BEGIN;")?;
+        let mut insert = self.db.prepare(
+            "INSERT INTO token_mint_balance (mint, amount, holders, accounts) VALUES (?, ?, ?, ?);",
+        )?;
+        for (mint, (amount, holders, accounts)) in mints {
+            // Balances of a valid mint never exceed its u64 supply.
+            let amount = u64::try_from(amount).unwrap_or(u64::MAX);
+            insert.execute(params![
+                mint.as_ref(),
+                amount as i64,
+                holders as i64,
+                accounts as i64
+            ])?;
+        }
+        self.db.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+
+    /// Aggregates accounts per owner.
+    ///
+    /// Like `token_mint_balance`, this is derived from `account` after all inserts,
+    /// so that replaced account versions aren't counted.
+    fn insert_owner_stats(&self) -> Result<()> {
+        info!("Summing accounts per owner");
+        self.db.execute(
+            "\
+INSERT INTO owner_stats (owner, account_count, total_lamports, total_data_len)
+    SELECT owner, COUNT(*), SUM(lamports), SUM(data_len) FROM account GROUP BY owner;",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn count_rows(&self) -> Result<Vec<(&'static str, u64)>> {
+        TABLES
+            .iter()
+            .map(|table| {
+                let count: i64 =
+                    self.db
+                        .query_row(&format!("SELECT COUNT(*) FROM {};", table), [], |row| {
+                            row.get(0)
+                        })?;
+                Ok((*table, count as u64))
+            })
+            .collect()
+    }
 }
 
-struct Worker<'a> {
-    db: &'a Connection,
-    progress: Arc<Progress>,
+/// Cuts `value` to at most `max_len` bytes at a char boundary, warning if it was longer.
+fn truncate_column<'a>(value: &'a str, max_len: usize, column: &str, pubkey: &Pubkey) -> &'a str {
+    if value.len() <= max_len {
+        return value;
+    }
+    warn!(
+        "Truncating token_metadata.{} of {} from {} to {} bytes",
+        column,
+        pubkey,
+        value.len(),
+        max_len
+    );
+    let mut end = max_len;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+/// Reads the total physical memory from `/proc/meminfo`, only available on Linux.
+fn total_memory_mib() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib / 1024)
+}
+
+/// Writes the SHA-256 of the DB in `sha256sum` format, followed by per-table row counts.
+fn write_checksum_file(db_path: &Path, row_counts: &[(&str, u64)]) -> Result<()> {
+    let mut hasher = Hasher::default();
+    let mut file = File::open(db_path)?;
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.hash(&buf[..n]);
+    }
+    let digest: String = hasher
+        .result()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let mut checksum_path = db_path.as_os_str().to_owned();
+    checksum_path.push(".sha256");
+    let mut checksum_file = File::create(&checksum_path)?;
+    writeln!(
+        checksum_file,
+        "{}  {}",
+        digest,
+        db_path.file_name().unwrap().to_string_lossy()
+    )?;
+    for (table, count) in row_counts {
+        writeln!(checksum_file, "# {} {}", table, count)?;
+    }
+    info!("Wrote checksum {} to {:?}", digest, checksum_path);
+    Ok(())
 }
 
-impl<'a> AppendVecConsumer for Worker<'a> {
-    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
-        for acc in append_vec_iter(Rc::new(append_vec)) {
-            self.insert_account(&acc.access().unwrap())?;
+impl AccountSink for SqliteIndexer {
+    fn begin_append_vec(&mut self, append_vec: &AppendVec) -> GenericResult<()> {
+        self.record_done()?;
+        if let Some(resume) = &mut self.resume {
+            let key = (append_vec.slot(), append_vec.id());
+            resume.skip = resume.done.contains(&key);
+            if !resume.skip {
+                resume.current = Some(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, account: &StoredAccountMeta) -> GenericResult<()> {
+        if self.resume.as_ref().is_some_and(|resume| resume.skip) {
+            return Ok(());
         }
+        let batch_len = match self.batch_len {
+            Some(batch_len) => batch_len,
+            None => {
+                self.db.execute_batch("BEGIN;")?;
+                0
+            }
+        };
+        let mut worker = Worker {
+            db: &self.db,
+            progress: &self.progress,
+            rent: &self.rent,
+            metadata_programs: &self.metadata_programs,
+            // An AppendVec's accounts must be committed together with its `done_appendvecs` row.
+            batch_size: if self.resume.is_some() {
+                u64::MAX
+            } else {
+                self.batch_size
+            },
+            batch_len,
+        };
+        let result = worker.insert_account(account);
+        self.batch_len = Some(worker.batch_len);
+        result
+    }
+
+    fn finish(self) -> GenericResult<()> {
+        let stats = self.finish_db()?;
+        info!("Done!");
+        info!("Dumped {} accounts", stats.accounts_total);
+        info!("Dumped {} token accounts", stats.token_accounts_total);
         Ok(())
     }
 }
 
+struct Worker<'a> {
+    db: &'a Connection,
+    progress: &'a Progress,
+    rent: &'a Rent,
+    metadata_programs: &'a [Pubkey],
+    batch_size: u64,
+    batch_len: u64,
+}
+
 impl<'a> Worker<'a> {
     fn insert_account(&mut self, account: &StoredAccountMeta) -> Result<()> {
         self.insert_account_meta(account)?;
         if account.account_meta.owner == spl_token::id() {
             self.insert_token(account)?;
         }
-        if account.account_meta.owner == mpl_metadata::id() {
+        if self.metadata_programs.contains(&account.account_meta.owner) {
             self.insert_token_metadata(account)?;
         }
         self.progress.accounts_counter.inc();
+        self.batch_len += 1;
+        if self.batch_len >= self.batch_size {
+            self.db.execute_batch("COMMIT; BEGIN;")?;
+            self.batch_len = 0;
+        }
         Ok(())
     }
 
     fn insert_account_meta(&mut self, account: &StoredAccountMeta) -> Result<()> {
         let mut account_insert = self.db.prepare_cached(
             "\
-INSERT OR REPLACE INTO account (pubkey, data_len, owner, lamports, executable, rent_epoch)
-    VALUES (?, ?, ?, ?, ?, ?);",
+INSERT OR REPLACE INTO account (pubkey, data_len, owner, lamports, executable, rent_epoch, rent_exempt)
+    VALUES (?, ?, ?, ?, ?, ?, ?);",
         )?;
         account_insert.insert(params![
             account.meta.pubkey.as_ref(),
@@ -228,23 +821,24 @@ INSERT OR REPLACE INTO account (pubkey, data_len, owner, lamports, executable, r
             account.account_meta.lamports as i64,
             account.account_meta.executable,
             account.account_meta.rent_epoch as i64,
+            is_rent_exempt(self.rent, account),
         ])?;
         Ok(())
     }
 
     fn insert_token(&mut self, account: &StoredAccountMeta) -> Result<()> {
-        match account.meta.data_len as usize {
-            spl_token::state::Account::LEN => {
+        match classify(account) {
+            AccountClass::TokenAccount => {
                 if let Ok(token_account) = spl_token::state::Account::unpack(account.data) {
                     self.insert_token_account(account, &token_account)?;
                 }
             }
-            spl_token::state::Mint::LEN => {
+            AccountClass::TokenMint => {
                 if let Ok(token_mint) = spl_token::state::Mint::unpack(account.data) {
                     self.insert_token_mint(account, &token_mint)?;
                 }
             }
-            spl_token::state::Multisig::LEN => {
+            AccountClass::TokenMultisig => {
                 if let Ok(token_multisig) = spl_token::state::Multisig::unpack(account.data) {
                     self.insert_token_multisig(account, &token_multisig)?;
                 }
@@ -353,6 +947,23 @@ INSERT OR REPLACE INTO token_multisig (pubkey, signer, m, n)
                     meta_v1_2.as_ref(),
                 )?;
             }
+            mpl_metadata::AccountKey::UseAuthorityRecord => {
+                let record = match mpl_metadata::UseAuthorityRecord::deserialize(&mut data_peek) {
+                    Ok(v) => v,
+                    Err(_) => return Ok(()),
+                };
+                self.insert_use_authority_record(account, &record)?;
+            }
+            mpl_metadata::AccountKey::CollectionAuthorityRecord => {
+                let record =
+                    match mpl_metadata::CollectionAuthorityRecord::deserialize(&mut data_peek) {
+                        Ok(v) => v,
+                        Err(_) => return Ok(()),
+                    };
+                let record_ext =
+                    mpl_metadata::CollectionAuthorityRecordExt::deserialize(&mut data_peek).ok();
+                self.insert_collection_authority_record(account, &record, record_ext.as_ref())?;
+            }
             _ => return Ok(()), // TODO
         }
         self.progress.metaplex_accounts_counter.inc();
@@ -366,7 +977,12 @@ INSERT OR REPLACE INTO token_multisig (pubkey, signer, m, n)
         meta_v1_1: Option<&mpl_metadata::MetadataExt>,
         meta_v1_2: Option<&mpl_metadata::MetadataExtV1_2>,
     ) -> Result<()> {
+        let pubkey = &account.meta.pubkey;
+        let name = truncate_column(&meta_v1.data.name.0, MAX_NAME_LEN, "name", pubkey);
+        let symbol = truncate_column(&meta_v1.data.symbol.0, MAX_SYMBOL_LEN, "symbol", pubkey);
+        let uri = truncate_column(&meta_v1.data.uri.0, MAX_URI_LEN, "uri", pubkey);
         let collection = meta_v1_2.as_ref().and_then(|m| m.collection.as_ref());
+        let uses = meta_v1_2.as_ref().and_then(|m| m.uses.as_ref());
         self.db
             .prepare_cached(
                 "\
@@ -381,21 +997,65 @@ INSERT OR REPLACE INTO token_metadata (
     is_mutable,
     edition_nonce,
     collection_verified,
-    collection_key
-) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+    collection_key,
+    uses_method,
+    uses_remaining,
+    uses_total
+) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
             )?
             .insert(params![
                 account.meta.pubkey.as_ref(),
                 meta_v1.mint.as_ref(),
-                meta_v1.data.name,
-                meta_v1.data.symbol,
-                meta_v1.data.uri,
+                name,
+                symbol,
+                uri,
                 meta_v1.data.seller_fee_basis_points,
                 meta_v1.primary_sale_happened,
                 meta_v1.is_mutable,
                 meta_v1_1.map(|c| c.edition_nonce),
                 collection.map(|c| c.verified),
                 collection.map(|c| c.key.as_ref()),
+                uses.map(|u| u.use_method),
+                uses.map(|u| u.remaining as i64),
+                uses.map(|u| u.total as i64),
+            ])?;
+        Ok(())
+    }
+
+    fn insert_use_authority_record(
+        &mut self,
+        account: &StoredAccountMeta,
+        record: &mpl_metadata::UseAuthorityRecord,
+    ) -> Result<()> {
+        self.db
+            .prepare_cached(
+                "\
+INSERT OR REPLACE INTO use_authority_record (pubkey, allowed_uses, bump) VALUES (?, ?, ?);",
+            )?
+            .insert(params![
+                account.meta.pubkey.as_ref(),
+                record.allowed_uses as i64,
+                record.bump,
+            ])?;
+        Ok(())
+    }
+
+    fn insert_collection_authority_record(
+        &mut self,
+        account: &StoredAccountMeta,
+        record: &mpl_metadata::CollectionAuthorityRecord,
+        record_ext: Option<&mpl_metadata::CollectionAuthorityRecordExt>,
+    ) -> Result<()> {
+        let update_authority = record_ext.and_then(|r| r.update_authority.as_ref());
+        self.db
+            .prepare_cached(
+                "\
+INSERT OR REPLACE INTO collection_authority_record (pubkey, bump, update_authority) VALUES (?, ?, ?);",
+            )?
+            .insert(params![
+                account.meta.pubkey.as_ref(),
+                record.bump,
+                update_authority.map(|k| k.as_ref()),
             ])?;
         Ok(())
     }
@@ -436,27 +1096,231 @@ impl Drop for ProgressCounter {
 
 struct TempFileGuard {
     pub path: Option<PathBuf>,
+    remove_on_drop: bool,
 }
 
 impl TempFileGuard {
     fn new(path: PathBuf) -> Self {
-        Self { path: Some(path) }
+        Self {
+            path: Some(path),
+            remove_on_drop: true,
+        }
+    }
+
+    /// Creates a guard that leaves the file in place if it isn't promoted, so it can be resumed.
+    fn kept_on_error(path: PathBuf) -> Self {
+        Self {
+            path: Some(path),
+            remove_on_drop: false,
+        }
     }
 
     fn promote<P: AsRef<Path>>(&mut self, new_name: P) -> std::io::Result<()> {
-        std::fs::rename(
-            self.path.take().expect("cannot promote non-existent file"),
-            new_name,
-        )
+        let path = self
+            .path
+            .as_ref()
+            .expect("cannot promote non-existent file");
+        let new_name = new_name.as_ref();
+        if std::fs::rename(path, new_name).is_err() {
+            // Renaming fails if the temp dir is on another file system.
+            info!("Copying temp DB to {}", new_name.display());
+            if let Err(e) = std::fs::copy(path, new_name) {
+                let _ = std::fs::remove_file(new_name);
+                return Err(e);
+            }
+            std::fs::remove_file(path)?;
+        }
+        self.path = None;
+        Ok(())
     }
 }
 
 impl Drop for TempFileGuard {
     fn drop(&mut self) {
         if let Some(path) = &self.path {
+            if !self.remove_on_drop {
+                warn!("Keeping temp DB {} to resume from", path.display());
+                return;
+            }
             if let Err(e) = std::fs::remove_file(path) {
                 error!("Failed to remove temp DB: {}", e);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indicatif::ProgressDrawTarget;
+    use solana_snapshot_etl::{append_vec_accounts, OwnedAccount};
+
+    fn indexer() -> SqliteIndexer {
+        let db = SqliteIndexer::create_db(Connection::open_in_memory().unwrap(), 0).unwrap();
+        SqliteIndexer::with_db(
+            db,
+            Destination::Memory { backup_path: None },
+            Rent::default(),
+            &MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        )
+    }
+
+    fn borsh_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+        out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        out.extend_from_slice(bytes);
+    }
+
+    /// Serializes a v1 metadata account without creators or extensions.
+    fn metadata(mint: &Pubkey, name: &[u8], symbol: &[u8], uri: &[u8]) -> Vec<u8> {
+        let mut data = vec![4]; // AccountKey::MetadataV1
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        borsh_bytes(&mut data, name);
+        borsh_bytes(&mut data, symbol);
+        borsh_bytes(&mut data, uri);
+        data.extend_from_slice(&500u16.to_le_bytes());
+        data.push(0); // creators
+        data.push(1); // primary_sale_happened
+        data.push(0); // is_mutable
+        data
+    }
+
+    /// Appends the edition nonce and the v1.2 extension: token standard, collection, and uses.
+    fn metadata_v1_2(mut data: Vec<u8>, collection: &Pubkey) -> Vec<u8> {
+        data.extend_from_slice(&[1, 254]); // edition_nonce
+        data.extend_from_slice(&[1, 0]); // token_standard
+        data.extend_from_slice(&[1, 1]); // collection, verified
+        data.extend_from_slice(collection.as_ref());
+        data.extend_from_slice(&[1, 2]); // uses, use_method
+        data.extend_from_slice(&3u64.to_le_bytes()); // remaining
+        data.extend_from_slice(&5u64.to_le_bytes()); // total
+        data
+    }
+
+    /// Edition nonce, collection verified and key, uses method, remaining, and total.
+    type ExtensionColumns = (
+        Option<u8>,
+        Option<bool>,
+        Option<Vec<u8>>,
+        Option<u8>,
+        Option<i64>,
+        Option<i64>,
+    );
+
+    fn extension_columns(indexer: &SqliteIndexer, pubkey: &Pubkey) -> ExtensionColumns {
+        indexer
+            .db
+            .query_row(
+                "\
+SELECT edition_nonce, collection_verified, collection_key, uses_method, uses_remaining, uses_total
+    FROM token_metadata WHERE pubkey = ?;",
+                [pubkey.as_ref()],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                    ))
+                },
+            )
+            .unwrap()
+    }
+
+    /// Indexes a metadata account, returning its pubkey.
+    fn insert_metadata(indexer: &mut SqliteIndexer, data: Vec<u8>) -> Pubkey {
+        let account = OwnedAccount {
+            pubkey: Pubkey::new_unique(),
+            owner: mpl_metadata::id(),
+            lamports: 1,
+            data,
+            executable: false,
+            rent_epoch: 0,
+            write_version: 1,
+        };
+        let append_vec =
+            AppendVec::new_from_accounts(std::slice::from_ref(&account), 0, 0).unwrap();
+        for account in append_vec_accounts(&append_vec) {
+            indexer.write(&account).unwrap();
+        }
+        account.pubkey
+    }
+
+    #[test]
+    fn truncate_column_cuts_at_char_boundary() {
+        let pubkey = Pubkey::new_unique();
+        assert_eq!(truncate_column("abc", 3, "name", &pubkey), "abc");
+        // The euro sign takes bytes 2..5, so a cut at 3 or 4 bytes drops it.
+        assert_eq!(truncate_column("ab€d", 3, "name", &pubkey), "ab");
+        assert_eq!(truncate_column("ab€d", 4, "name", &pubkey), "ab");
+        assert_eq!(truncate_column("ab€d", 5, "name", &pubkey), "ab€");
+    }
+
+    #[test]
+    fn overlong_metadata_strings_are_truncated() {
+        let mut indexer = indexer();
+        let mint = Pubkey::new_unique();
+        // Invalid UTF-8 becomes U+FFFD, 3 bytes each, so 12 of them exceed the 32 byte name.
+        let name = [0xffu8; 12];
+        let pubkey = insert_metadata(
+            &mut indexer,
+            metadata(&mint, &name, b"SYMBOL_TOO_LONG", &[b'u'; 300]),
+        );
+        let (name, symbol, uri): (String, String, String) = indexer
+            .db
+            .query_row(
+                "SELECT name, symbol, uri FROM token_metadata WHERE pubkey = ?;",
+                [pubkey.as_ref()],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(name, "\u{fffd}".repeat(10));
+        assert_eq!(symbol, "SYMBOL_TOO");
+        assert_eq!(uri, "u".repeat(MAX_URI_LEN));
+    }
+
+    #[test]
+    fn metadata_extension_fills_collection_and_uses() {
+        let mut indexer = indexer();
+        let collection = Pubkey::new_unique();
+        let data = metadata_v1_2(
+            metadata(
+                &Pubkey::new_unique(),
+                b"Name",
+                b"SYM",
+                b"https://example.com",
+            ),
+            &collection,
+        );
+        let pubkey = insert_metadata(&mut indexer, data);
+        assert_eq!(
+            extension_columns(&indexer, &pubkey),
+            (
+                Some(254),
+                Some(true),
+                Some(collection.to_bytes().to_vec()),
+                Some(2),
+                Some(3),
+                Some(5)
+            )
+        );
+    }
+
+    #[test]
+    fn metadata_without_extension_has_null_columns() {
+        let mut indexer = indexer();
+        let data = metadata(
+            &Pubkey::new_unique(),
+            b"Name",
+            b"SYM",
+            b"https://example.com",
+        );
+        let pubkey = insert_metadata(&mut indexer, data);
+        assert_eq!(
+            extension_columns(&indexer, &pubkey),
+            (None, None, None, None, None, None)
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use log::{debug, info};
+use solana_snapshot_etl::append_vec::AppendVec;
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+/// Number of AppendVecs listed in the summary.
+const SLOWEST_LEN: usize = 10;
+
+struct AppendVecTiming {
+    slot: u64,
+    id: u64,
+    accounts: u64,
+    bytes: usize,
+    elapsed: Duration,
+}
+
+/// Measures how long consumers spend on each AppendVec.
+///
+/// An AppendVec counts as processed once the next one is requested.
+#[derive(Default)]
+pub(crate) struct Timings {
+    current: Option<(AppendVecTiming, Instant)>,
+    finished: Vec<AppendVecTiming>,
+}
+
+impl Timings {
+    /// Stops the clock of the previous AppendVec and starts it for this one.
+    pub(crate) fn start(&mut self, append_vec: &AppendVec) {
+        self.finish();
+        let mut accounts = 0u64;
+        let mut offset = 0usize;
+        while let Some((_, next_offset)) = append_vec.get_account(offset) {
+            accounts += 1;
+            offset = next_offset;
+        }
+        let timing = AppendVecTiming {
+            slot: append_vec.slot(),
+            id: append_vec.id(),
+            accounts,
+            bytes: append_vec.len(),
+            elapsed: Duration::ZERO,
+        };
+        self.current = Some((timing, Instant::now()));
+    }
+
+    /// Stops the clock of the AppendVec being processed, if any.
+    pub(crate) fn finish(&mut self) {
+        if let Some((mut timing, start)) = self.current.take() {
+            timing.elapsed = start.elapsed();
+            debug!(
+                "AppendVec {}.{}: {} accounts, {} bytes, {:?}",
+                timing.slot, timing.id, timing.accounts, timing.bytes, timing.elapsed
+            );
+            self.finished.push(timing);
+        }
+    }
+
+    /// Logs the slowest AppendVecs.
+    pub(crate) fn log_summary(&mut self) {
+        self.finish();
+        if self.finished.is_empty() {
+            return;
+        }
+        let total: Duration = self.finished.iter().map(|t| t.elapsed).sum();
+        info!(
+            "Processed {} AppendVecs in {:?}, slowest:",
+            self.finished.len(),
+            total
+        );
+        self.finished
+            .sort_unstable_by_key(|timing| Reverse(timing.elapsed));
+        for timing in self.finished.iter().take(SLOWEST_LEN) {
+            info!(
+                "  AppendVec {}.{}: {} accounts, {} bytes, {:?}",
+                timing.slot, timing.id, timing.accounts, timing.bytes, timing.elapsed
+            );
+        }
+    }
+}
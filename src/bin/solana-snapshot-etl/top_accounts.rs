@@ -0,0 +1,115 @@
+use crate::filter::AccountFilter;
+use crate::program_names::ProgramNames;
+use clap::ValueEnum;
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::append_vec_accounts;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::Write;
+
+/// Field that accounts are ranked by in `--top-accounts`.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub(crate) enum TopAccountsKey {
+    #[default]
+    DataLen,
+    Lamports,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct TopAccount {
+    /// Value of the ranking field, compared first.
+    key: u64,
+    pubkey: Pubkey,
+    owner: Pubkey,
+    data_len: u64,
+    lamports: u64,
+}
+
+/// Keeps the N largest accounts in a min-heap, so memory doesn't grow with the snapshot.
+///
+/// Every stored version is ranked, so an account may be listed once per version.
+pub(crate) struct TopAccounts {
+    heap: BinaryHeap<Reverse<TopAccount>>,
+    limit: usize,
+    key: TopAccountsKey,
+    filter: AccountFilter,
+}
+
+impl AppendVecConsumer for TopAccounts {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            if !self.filter.matches(&account) {
+                continue;
+            }
+            let key = match self.key {
+                TopAccountsKey::DataLen => account.meta.data_len,
+                TopAccountsKey::Lamports => account.account_meta.lamports,
+            };
+            // Most accounts are smaller than all kept ones, skip them without touching the heap.
+            if self.heap.len() >= self.limit
+                && self.heap.peek().is_none_or(|Reverse(min)| key <= min.key)
+            {
+                continue;
+            }
+            self.heap.push(Reverse(TopAccount {
+                key,
+                pubkey: account.meta.pubkey,
+                owner: account.account_meta.owner,
+                data_len: account.meta.data_len,
+                lamports: account.account_meta.lamports,
+            }));
+            if self.heap.len() > self.limit {
+                self.heap.pop();
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TopAccounts {
+    pub(crate) fn new(limit: usize, key: TopAccountsKey, filter: AccountFilter) -> Self {
+        Self {
+            heap: BinaryHeap::with_capacity(limit + 1),
+            limit,
+            key,
+            filter,
+        }
+    }
+
+    /// Prints the kept accounts, largest first.
+    ///
+    /// With `program_names`, a column with the owner program's name is appended.
+    pub(crate) fn write_table<W: Write>(
+        self,
+        mut writer: W,
+        program_names: Option<&ProgramNames>,
+    ) -> GenericResult<()> {
+        write!(
+            writer,
+            "{:<44} {:<44} {:>12} {:>20}",
+            "pubkey", "owner", "data_len", "lamports"
+        )?;
+        if program_names.is_some() {
+            write!(writer, "  program")?;
+        }
+        writeln!(writer)?;
+        // Ascending order of `Reverse` is descending order of the accounts.
+        for Reverse(account) in self.heap.into_sorted_vec() {
+            write!(
+                writer,
+                "{:<44} {:<44} {:>12} {:>20}",
+                account.pubkey.to_string(),
+                account.owner.to_string(),
+                account.data_len,
+                account.lamports
+            )?;
+            if let Some(program_names) = program_names {
+                write!(writer, "  {}", program_names.name(&account.owner))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
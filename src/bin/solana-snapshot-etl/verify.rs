@@ -0,0 +1,192 @@
+use crossbeam::channel::{Receiver, Sender};
+use log::{error, info};
+use solana_runtime::accounts_db::AccountsDb;
+use solana_runtime::accounts_hash::AccountsHash;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, AppendVecConsumerFactory, GenericResult};
+use solana_snapshot_etl::{append_vec_accounts, BankSummary};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Maps pubkey to (write version, account hash) of the latest version in a slot.
+type SlotAccounts = HashMap<Pubkey, (u64, Hash)>;
+
+/// Recomputes account hashes, and the accounts delta hash and bank hash of the snapshot slot.
+///
+/// Hands out one [`AccountHasher`] per thread for use with `par_iter_append_vecs`.
+pub(crate) struct BankHashVerifier {
+    shared: Arc<Shared>,
+    mismatch_rx: Receiver<HashMismatch>,
+}
+
+/// State shared by all hashing threads.
+struct Shared {
+    slot: u64,
+    max_mismatches: u64,
+    accounts: Mutex<SlotAccounts>,
+    accounts_count: AtomicU64,
+    mismatches_count: AtomicU64,
+    mismatch_tx: Sender<HashMismatch>,
+}
+
+/// An account whose stored hash differs from the recomputed one.
+pub(crate) struct HashMismatch {
+    slot: u64,
+    id: u64,
+    pubkey: Pubkey,
+    stored: Hash,
+    computed: Hash,
+}
+
+impl fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AppendVec {}.{}: account {} has hash {}, expected {}",
+            self.slot, self.id, self.pubkey, self.stored, self.computed
+        )
+    }
+}
+
+pub(crate) struct AccountHasher {
+    shared: Arc<Shared>,
+}
+
+impl AppendVecConsumer for AccountHasher {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        let (slot, id) = (append_vec.slot(), append_vec.id());
+        let mut slot_accounts = SlotAccounts::new();
+        let mut accounts_count = 0u64;
+        for account in append_vec_accounts(&append_vec) {
+            accounts_count += 1;
+            let computed = AccountsDb::hash_account(slot, &account, &account.meta.pubkey);
+            if computed != *account.hash {
+                self.shared.report(HashMismatch {
+                    slot,
+                    id,
+                    pubkey: account.meta.pubkey,
+                    stored: *account.hash,
+                    computed,
+                });
+            }
+            if slot == self.shared.slot {
+                insert_latest(
+                    &mut slot_accounts,
+                    account.meta.pubkey,
+                    (account.meta.write_version, *account.hash),
+                );
+            }
+        }
+        self.shared
+            .accounts_count
+            .fetch_add(accounts_count, Ordering::Relaxed);
+        if !slot_accounts.is_empty() {
+            let mut accounts = self.shared.accounts.lock().unwrap();
+            for (pubkey, version) in slot_accounts {
+                insert_latest(&mut accounts, pubkey, version);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AppendVecConsumerFactory for BankHashVerifier {
+    type Consumer = AccountHasher;
+
+    fn new_consumer(&mut self) -> GenericResult<Self::Consumer> {
+        Ok(AccountHasher {
+            shared: Arc::clone(&self.shared),
+        })
+    }
+}
+
+impl Shared {
+    fn report(&self, mismatch: HashMismatch) {
+        // Only keep the first few, a wrong hash function would otherwise flood the channel.
+        if self.mismatches_count.fetch_add(1, Ordering::Relaxed) < self.max_mismatches {
+            let _ = self.mismatch_tx.send(mismatch);
+        }
+    }
+}
+
+fn insert_latest(accounts: &mut SlotAccounts, pubkey: Pubkey, version: (u64, Hash)) {
+    match accounts.entry(pubkey) {
+        Entry::Vacant(e) => {
+            e.insert(version);
+        }
+        Entry::Occupied(mut e) => {
+            if version.0 > e.get().0 {
+                e.insert(version);
+            }
+        }
+    }
+}
+
+impl BankHashVerifier {
+    /// Logs at most `max_mismatches` accounts with a wrong hash.
+    pub(crate) fn new(slot: u64, max_mismatches: u64) -> Self {
+        let (mismatch_tx, mismatch_rx) = crossbeam::channel::unbounded();
+        Self {
+            shared: Arc::new(Shared {
+                slot,
+                max_mismatches,
+                accounts: Mutex::new(SlotAccounts::new()),
+                accounts_count: AtomicU64::new(0),
+                mismatches_count: AtomicU64::new(0),
+                mismatch_tx,
+            }),
+            mismatch_rx,
+        }
+    }
+
+    /// Compares the recomputed hashes against the ones stored in the snapshot.
+    ///
+    /// Must be called after all consumers are done.
+    pub(crate) fn verify(self, bank: &BankSummary) -> GenericResult<()> {
+        let shared =
+            Arc::try_unwrap(self.shared).map_err(|_| "account hashers are still running")?;
+        drop(shared.mismatch_tx);
+        for mismatch in self.mismatch_rx.iter() {
+            error!("{}", mismatch);
+        }
+        let mismatches_count = shared.mismatches_count.into_inner();
+        info!(
+            "Hashed {} accounts, {} mismatches",
+            shared.accounts_count.into_inner(),
+            mismatches_count
+        );
+
+        let accounts = shared.accounts.into_inner().unwrap();
+        info!(
+            "Hashing {} accounts written in slot {}",
+            accounts.len(),
+            shared.slot
+        );
+        let hashes = accounts
+            .into_iter()
+            .map(|(pubkey, (_, hash))| (pubkey, hash))
+            .collect();
+        let accounts_delta_hash = AccountsHash::accumulate_account_hashes(hashes);
+        let bank_hash = bank.compute_bank_hash(&accounts_delta_hash);
+        info!(
+            "Accounts delta hash: {} (snapshot: {})",
+            accounts_delta_hash, bank.accounts_delta_hash
+        );
+        info!("Bank hash: {} (snapshot: {})", bank_hash, bank.hash);
+        if mismatches_count > 0 {
+            return Err(format!("{} account hash mismatches", mismatches_count).into());
+        }
+        if accounts_delta_hash != bank.accounts_delta_hash {
+            return Err("Accounts delta hash mismatch".into());
+        }
+        if bank_hash != bank.hash {
+            return Err("Bank hash mismatch".into());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use log::info;
+use solana_sdk::native_token::lamports_to_sol;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_program;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::parallel::{AppendVecConsumer, GenericResult};
+use solana_snapshot_etl::{append_vec_accounts, LatestVersions};
+use std::cmp::Reverse;
+use std::io::Write;
+
+#[derive(Clone, Copy)]
+struct WalletVersion {
+    lamports: u64,
+    system_owned: bool,
+}
+
+/// Collects the SOL balances of System Program accounts.
+///
+/// Accounts are indexed regardless of owner, since a wallet is only live
+/// if its latest version is still owned by the System Program.
+#[derive(Default)]
+pub(crate) struct WalletReport {
+    accounts: LatestVersions<WalletVersion>,
+}
+
+impl AppendVecConsumer for WalletReport {
+    fn on_append_vec(&mut self, append_vec: AppendVec) -> GenericResult<()> {
+        for account in append_vec_accounts(&append_vec) {
+            let version = WalletVersion {
+                lamports: account.account_meta.lamports,
+                system_owned: system_program::check_id(&account.account_meta.owner),
+            };
+            self.accounts
+                .insert(account.meta.pubkey, account.meta.write_version, version);
+        }
+        Ok(())
+    }
+}
+
+impl WalletReport {
+    /// Logs the wallet count and total balance, then prints wallets sorted by balance.
+    pub(crate) fn write_table<W: Write>(
+        self,
+        mut writer: W,
+        top: Option<usize>,
+    ) -> GenericResult<()> {
+        let mut wallets: Vec<(Pubkey, u64)> = self
+            .accounts
+            .into_iter()
+            .filter(|(_, version)| version.system_owned && version.lamports > 0)
+            .map(|(pubkey, version)| (pubkey, version.lamports))
+            .collect();
+        let total_lamports: u64 = wallets.iter().map(|(_, lamports)| lamports).sum();
+        info!(
+            "{} wallets holding {} SOL",
+            wallets.len(),
+            lamports_to_sol(total_lamports)
+        );
+        wallets.sort_unstable_by_key(|(_, lamports)| Reverse(*lamports));
+        writeln!(writer, "{:<44} {:>20} {:>20}", "pubkey", "lamports", "sol")?;
+        for (pubkey, lamports) in wallets.into_iter().take(top.unwrap_or(usize::MAX)) {
+            writeln!(
+                writer,
+                "{:<44} {:>20} {:>20}",
+                pubkey.to_string(),
+                lamports,
+                lamports_to_sol(lamports)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+//! Reads back the account records written by `solana-snapshot-etl --bincode-out`.
+
+use crate::append_vec::AppendVec;
+use crate::{AppendVecIterator, OwnedAccount, Result, SnapshotExtractor};
+use std::io::{self, Read};
+
+/// Accounts are packed into AppendVecs of about this many bytes.
+const APPEND_VEC_SIZE: usize = 16 * 1024 * 1024;
+
+/// Extracts accounts from a stream of bincode-encoded [`OwnedAccount`] records,
+/// each preceded by its length as a big-endian u32.
+///
+/// The stream doesn't keep the AppendVecs of the original snapshot, so accounts are
+/// packed into new AppendVecs with slot 0 and increasing IDs, in stream order.
+///
+/// ```
+/// use solana_sdk::pubkey::Pubkey;
+/// use solana_snapshot_etl::bincode_stream::BincodeSnapshotExtractor;
+/// use solana_snapshot_etl::{for_each_account, OwnedAccount};
+///
+/// let account = OwnedAccount {
+///     pubkey: Pubkey::new_unique(),
+///     owner: Pubkey::new_unique(),
+///     lamports: 1_000_000,
+///     data: vec![1, 2, 3],
+///     executable: false,
+///     rent_epoch: 42,
+///     write_version: 7,
+/// };
+/// let record = bincode::serialize(&account).unwrap();
+/// let mut stream = (record.len() as u32).to_be_bytes().to_vec();
+/// stream.extend_from_slice(&record);
+///
+/// let mut extractor = BincodeSnapshotExtractor::new(stream.as_slice());
+/// let mut accounts = Vec::new();
+/// for_each_account(&mut extractor, |stored| {
+///     accounts.push(OwnedAccount::from(stored));
+///     Ok(())
+/// })
+/// .unwrap();
+/// assert_eq!(accounts, [account]);
+/// ```
+pub struct BincodeSnapshotExtractor<R> {
+    reader: R,
+    next_id: u64,
+    /// Set once the stream ended or failed.
+    done: bool,
+}
+
+impl<R: Read> SnapshotExtractor for BincodeSnapshotExtractor<R> {
+    fn iter(&mut self) -> AppendVecIterator<'_> {
+        Box::new(std::iter::from_fn(move || {
+            if self.done {
+                return None;
+            }
+            let append_vec = self.next_append_vec();
+            if !matches!(append_vec, Ok(Some(_))) {
+                self.done = true;
+            }
+            append_vec.transpose()
+        }))
+    }
+}
+
+impl<R: Read> BincodeSnapshotExtractor<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            next_id: 0,
+            done: false,
+        }
+    }
+
+    /// Reads records until they fill an AppendVec, or the stream ends.
+    fn next_append_vec(&mut self) -> Result<Option<AppendVec>> {
+        let mut accounts = Vec::new();
+        let mut size = 0;
+        while size < APPEND_VEC_SIZE {
+            match self.read_record()? {
+                Some(account) => {
+                    size += AppendVec::stored_size(account.data.len());
+                    accounts.push(account);
+                }
+                None => break,
+            }
+        }
+        if accounts.is_empty() {
+            return Ok(None);
+        }
+        let append_vec = AppendVec::new_from_accounts(&accounts, 0, self.next_id)?;
+        self.next_id += 1;
+        Ok(Some(append_vec))
+    }
+
+    /// Returns the next record, or `None` at the end of the stream.
+    fn read_record(&mut self) -> Result<Option<OwnedAccount>> {
+        let mut len = [0u8; 4];
+        let mut filled = 0;
+        while filled < len.len() {
+            match self.reader.read(&mut len[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let len = u32::from_be_bytes(len) as u64;
+        let mut record = (&mut self.reader).take(len);
+        let account = bincode::deserialize_from(&mut record)?;
+        if record.limit() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} trailing bytes after account record", record.limit()),
+            )
+            .into());
+        }
+        Ok(Some(account))
+    }
+}
@@ -0,0 +1,117 @@
+//! Bloom filter over account pubkeys, as a compact "does this account exist" oracle.
+//!
+//! The serialized format is the magic `SNAPBLM1`, the number of bits (u64 LE),
+//! the number of hash functions (u32 LE), then the bit array as u64 LE words.
+
+use solana_sdk::pubkey::Pubkey;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"SNAPBLM1";
+
+pub struct PubkeyBloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl PubkeyBloomFilter {
+    /// Sizes a filter for `items` pubkeys at the given false positive rate.
+    pub fn with_rate(items: u64, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-items * false_positive_rate.ln() / (ln2 * ln2)).ceil() as u64;
+        let num_bits = num_bits.max(64);
+        let num_hashes = ((num_bits as f64 / items) * ln2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0u64; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    pub fn insert(&mut self, pubkey: &Pubkey) {
+        for bit in self.bit_indexes(pubkey) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns false if the pubkey was never inserted, true if it probably was.
+    pub fn contains(&self, pubkey: &Pubkey) -> bool {
+        self.bit_indexes(pubkey)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Size of the bit array in bytes.
+    pub fn size_bytes(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// Derives the bit positions by double hashing.
+    ///
+    /// Pubkeys are hashed first, since vanity addresses are not uniformly distributed.
+    fn bit_indexes(&self, pubkey: &Pubkey) -> impl Iterator<Item = u64> {
+        let hash = blake3::hash(pubkey.as_ref());
+        let hash = hash.as_bytes();
+        let h1 = u64::from_le_bytes(hash[0..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(hash[8..16].try_into().unwrap());
+        let num_bits = self.num_bits;
+        (0..self.num_hashes as u64).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % num_bits)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.num_bits.to_le_bytes())?;
+        writer.write_all(&self.num_hashes.to_le_bytes())?;
+        for word in &self.bits {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Self> {
+        let mut reader = BufReader::new(reader);
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a pubkey bloom filter",
+            ));
+        }
+        let mut num_bits = [0u8; 8];
+        reader.read_exact(&mut num_bits)?;
+        let num_bits = u64::from_le_bytes(num_bits);
+        let mut num_hashes = [0u8; 4];
+        reader.read_exact(&mut num_hashes)?;
+        let num_hashes = u32::from_le_bytes(num_hashes);
+        if num_bits == 0 || num_hashes == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "empty bloom filter",
+            ));
+        }
+        let mut bits = vec![0u64; num_bits.div_ceil(64) as usize];
+        let mut word = [0u8; 8];
+        for bits_word in &mut bits {
+            reader.read_exact(&mut word)?;
+            *bits_word = u64::from_le_bytes(word);
+        }
+        Ok(Self {
+            bits,
+            num_bits,
+            num_hashes,
+        })
+    }
+
+    /// Loads a filter written by `--bloom-out`.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::read_from(File::open(path)?)
+    }
+}
@@ -0,0 +1,108 @@
+//! Labels accounts with their type, based on the owner program and data size.
+
+use crate::append_vec::StoredAccountMeta;
+use solana_sdk::{
+    bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable, stake, system_program, vote,
+};
+use std::fmt::{Display, Formatter};
+
+/// SPL Token program, whose account sizes are fixed.
+pub mod spl_token {
+    solana_sdk::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+    pub const ACCOUNT_LEN: usize = 165;
+    pub const MINT_LEN: usize = 82;
+    pub const MULTISIG_LEN: usize = 355;
+}
+
+/// Metaplex Token Metadata program.
+pub mod mpl_metadata {
+    solana_sdk::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+/// Bincode enum tags of `UpgradeableLoaderState`.
+const UPGRADEABLE_PROGRAM_TAG: u32 = 2;
+const UPGRADEABLE_PROGRAMDATA_TAG: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccountClass {
+    /// Owned by the System Program, e.g. wallets and nonce accounts.
+    System,
+    TokenAccount,
+    TokenMint,
+    TokenMultisig,
+    /// Owned by the Token Metadata program.
+    Metadata,
+    /// Executable program, or the program account of an upgradeable program.
+    Program,
+    /// Code of an upgradeable program.
+    ProgramData,
+    Vote,
+    Stake,
+    Unknown,
+}
+
+impl AccountClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountClass::System => "system",
+            AccountClass::TokenAccount => "token-account",
+            AccountClass::TokenMint => "token-mint",
+            AccountClass::TokenMultisig => "token-multisig",
+            AccountClass::Metadata => "metadata",
+            AccountClass::Program => "program",
+            AccountClass::ProgramData => "programdata",
+            AccountClass::Vote => "vote",
+            AccountClass::Stake => "stake",
+            AccountClass::Unknown => "unknown",
+        }
+    }
+}
+
+impl Display for AccountClass {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Classifies an account by its owner, and for SPL Token and loader accounts by their data.
+///
+/// Data is not validated beyond its size or enum tag,
+/// so e.g. an uninitialized token account is still a `TokenAccount`.
+pub fn classify(account: &StoredAccountMeta) -> AccountClass {
+    let owner = &account.account_meta.owner;
+    if system_program::check_id(owner) {
+        AccountClass::System
+    } else if spl_token::check_id(owner) {
+        match account.meta.data_len as usize {
+            spl_token::ACCOUNT_LEN => AccountClass::TokenAccount,
+            spl_token::MINT_LEN => AccountClass::TokenMint,
+            spl_token::MULTISIG_LEN => AccountClass::TokenMultisig,
+            _ => AccountClass::Unknown,
+        }
+    } else if mpl_metadata::check_id(owner) {
+        AccountClass::Metadata
+    } else if bpf_loader::check_id(owner) || bpf_loader_deprecated::check_id(owner) {
+        if account.account_meta.executable {
+            AccountClass::Program
+        } else {
+            AccountClass::Unknown
+        }
+    } else if bpf_loader_upgradeable::check_id(owner) {
+        let tag = account
+            .data
+            .get(..4)
+            .map(|tag| u32::from_le_bytes(tag.try_into().unwrap()));
+        match tag {
+            Some(UPGRADEABLE_PROGRAM_TAG) => AccountClass::Program,
+            Some(UPGRADEABLE_PROGRAMDATA_TAG) => AccountClass::ProgramData,
+            _ => AccountClass::Unknown,
+        }
+    } else if vote::program::check_id(owner) {
+        AccountClass::Vote
+    } else if stake::program::check_id(owner) {
+        AccountClass::Stake
+    } else {
+        AccountClass::Unknown
+    }
+}
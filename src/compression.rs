@@ -0,0 +1,124 @@
+use std::ffi::OsStr;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression codecs that snapshot files may be wrapped in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Bzip2,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    /// Identifies a codec by the magic bytes at the start of a stream.
+    pub fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(BZIP2_MAGIC) {
+            Some(Self::Bzip2)
+        } else if header.starts_with(GZIP_MAGIC) {
+            Some(Self::Gzip)
+        } else if header.starts_with(XZ_MAGIC) {
+            Some(Self::Xz)
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Identifies a codec by the file extension.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension().and_then(OsStr::to_str)? {
+            "bz2" => Some(Self::Bzip2),
+            "gz" => Some(Self::Gzip),
+            "xz" => Some(Self::Xz),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Detects the codec of a stream without consuming any bytes.
+    ///
+    /// Magic bytes take precedence, the file extension is used as a fallback.
+    /// Streams matching neither are assumed to be uncompressed.
+    pub fn detect<R: BufRead>(rd: &mut R, path: &Path) -> io::Result<Self> {
+        let header = rd.fill_buf()?;
+        Ok(Self::from_magic(header)
+            .or_else(|| Self::from_extension(path))
+            .unwrap_or(Self::None))
+    }
+
+    /// Wraps a reader with the matching decoder.
+    ///
+    /// Codecs other than zstd fail with [`io::ErrorKind::Unsupported`]
+    /// unless their feature (`bzip2`, `gzip`, or `xz`) is enabled.
+    pub fn decoder<'a, R: BufRead + Send + 'a>(
+        self,
+        rd: R,
+    ) -> io::Result<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            Self::None => Box::new(rd),
+            #[cfg(feature = "bzip2")]
+            Self::Bzip2 => Box::new(bzip2::bufread::MultiBzDecoder::new(rd)),
+            #[cfg(feature = "gzip")]
+            Self::Gzip => Box::new(flate2::bufread::MultiGzDecoder::new(rd)),
+            #[cfg(feature = "xz")]
+            Self::Xz => Box::new(xz2::bufread::XzDecoder::new_multi_decoder(rd)),
+            Self::Zstd => Box::new(zstd::stream::read::Decoder::with_buffer(rd)?),
+            #[cfg(not(feature = "bzip2"))]
+            Self::Bzip2 => return Err(unsupported("bzip2")),
+            #[cfg(not(feature = "gzip"))]
+            Self::Gzip => return Err(unsupported("gzip")),
+            #[cfg(not(feature = "xz"))]
+            Self::Xz => return Err(unsupported("xz")),
+        })
+    }
+}
+
+/// Error for a codec whose feature, named after it, is disabled.
+#[cfg(not(all(feature = "bzip2", feature = "gzip", feature = "xz")))]
+fn unsupported(codec: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{0} archives are not supported, rebuild with the {0} feature",
+            codec
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_takes_precedence_over_extension() {
+        let gzip = [0x1f, 0x8b, 0x08, 0x00];
+        let detect = |header: &[u8], path: &str| {
+            Compression::detect(&mut &header[..], Path::new(path)).unwrap()
+        };
+        assert_eq!(detect(&gzip, "snapshot.tar.zst"), Compression::Gzip);
+        assert_eq!(detect(b"BZh9", "snapshot.tar.gz"), Compression::Bzip2);
+        assert_eq!(detect(b"plain", "snapshot.tar.gz"), Compression::Gzip);
+        assert_eq!(detect(b"plain", "snapshot.tar.bz2"), Compression::Bzip2);
+        assert_eq!(detect(b"plain", "snapshot.tar.zst"), Compression::Zstd);
+        assert_eq!(detect(b"plain", "snapshot.tar"), Compression::None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "gzip"))]
+    fn disabled_codec_is_unsupported() {
+        let err = match Compression::Gzip.decoder(&[0x1f, 0x8b][..]) {
+            Ok(_) => panic!("gzip decoder without the gzip feature"),
+            Err(err) => err,
+        };
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::loader::HttpOptions;
+use crate::{Result, SnapshotError};
+use log::warn;
+use percent_encoding::percent_decode_str;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Proxy, StatusCode, Url};
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Sends a GET request, retrying on server errors and connection failures.
+///
+/// Only establishing the response is retried, the body is streamed as-is.
+/// Credentials embedded in the URL are sent as basic auth.
+pub(crate) fn get(url: &str, options: &HttpOptions) -> Result<Response> {
+    let mut url =
+        Url::parse(url).map_err(|e| SnapshotError::InvalidSource(format!("bad URL: {}", e)))?;
+    let credentials = take_credentials(&mut url);
+    let headers = header_map(&options.headers)?;
+    let mut client = Client::builder()
+        .connect_timeout(options.timeout)
+        .timeout(options.timeout);
+    if let Some(proxy) = proxy(options)? {
+        client = client.proxy(proxy);
+    }
+    let client = client.build()?;
+    let mut attempt = 0;
+    loop {
+        let mut request = client.get(url.clone()).headers(headers.clone());
+        if let Some((username, password)) = &credentials {
+            request = request.basic_auth(username, password.as_ref());
+        }
+        let result = request.send().and_then(|resp| resp.error_for_status());
+        match result {
+            Err(e) if attempt < options.retries && is_retryable(&e) => {
+                attempt += 1;
+                let backoff = Duration::from_secs(1 << attempt.min(5));
+                warn!(
+                    "HTTP request failed, retrying in {:?} ({}/{}): {}",
+                    backoff, attempt, options.retries, e
+                );
+                std::thread::sleep(backoff);
+            }
+            result => return result.map_err(Into::into),
+        }
+    }
+}
+
+/// Async version of [`get`].
+///
+/// Only the connect timeout applies, the async client has no per-read timeout.
+#[cfg(feature = "async")]
+pub(crate) async fn get_async(url: &str, options: &HttpOptions) -> Result<reqwest::Response> {
+    let mut url =
+        Url::parse(url).map_err(|e| SnapshotError::InvalidSource(format!("bad URL: {}", e)))?;
+    let credentials = take_credentials(&mut url);
+    let headers = header_map(&options.headers)?;
+    let mut client = reqwest::Client::builder().connect_timeout(options.timeout);
+    if let Some(proxy) = proxy(options)? {
+        client = client.proxy(proxy);
+    }
+    let client = client.build()?;
+    let mut attempt = 0;
+    loop {
+        let mut request = client.get(url.clone()).headers(headers.clone());
+        if let Some((username, password)) = &credentials {
+            request = request.basic_auth(username, password.as_ref());
+        }
+        let result = request
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        match result {
+            Err(e) if attempt < options.retries && is_retryable(&e) => {
+                attempt += 1;
+                let backoff = Duration::from_secs(1 << attempt.min(5));
+                warn!(
+                    "HTTP request failed, retrying in {:?} ({}/{}): {}",
+                    backoff, attempt, options.retries, e
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            result => return result.map_err(Into::into),
+        }
+    }
+}
+
+fn is_retryable(e: &reqwest::Error) -> bool {
+    match e.status() {
+        Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => e.is_connect() || e.is_timeout() || e.is_request(),
+    }
+}
+
+/// Picks the proxy for requests, or `None` to connect directly.
+///
+/// [`HttpOptions::proxy`] applies to all requests and overrides the environment.
+/// Otherwise `HTTPS_PROXY` or `HTTP_PROXY` (by URL scheme) is used, falling back to `ALL_PROXY`,
+/// except for hosts listed in `NO_PROXY`. Lowercase variable names are accepted too.
+fn proxy(options: &HttpOptions) -> Result<Option<Proxy>> {
+    if let Some(proxy) = &options.proxy {
+        return Proxy::all(proxy.as_str())
+            .map(Some)
+            .map_err(|e| SnapshotError::InvalidSource(format!("bad proxy URL: {}", e)));
+    }
+    let http = proxy_env_var("HTTP_PROXY")?;
+    let https = proxy_env_var("HTTPS_PROXY")?;
+    let all = proxy_env_var("ALL_PROXY")?;
+    if http.is_none() && https.is_none() && all.is_none() {
+        return Ok(None);
+    }
+    let no_proxy = env_var("NO_PROXY").unwrap_or_default();
+    Ok(Some(Proxy::custom(move |url| {
+        if is_no_proxy_host(&no_proxy, url.host_str()?) {
+            return None;
+        }
+        let proxy = match url.scheme() {
+            "https" => https.as_ref(),
+            "http" => http.as_ref(),
+            _ => None,
+        };
+        proxy.or(all.as_ref()).cloned()
+    })))
+}
+
+/// Reads an environment variable by its uppercase or lowercase name, ignoring empty values.
+fn env_var(name: &str) -> Option<String> {
+    env::var(name)
+        .or_else(|_| env::var(name.to_lowercase()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads a proxy URL from the environment, checking that it can be used.
+fn proxy_env_var(name: &str) -> Result<Option<String>> {
+    match env_var(name) {
+        Some(value) => match Proxy::all(value.as_str()) {
+            Ok(_) => Ok(Some(value)),
+            Err(e) => Err(SnapshotError::InvalidSource(format!(
+                "bad proxy URL in {}: {}",
+                name, e
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Matches a host against a comma-separated `NO_PROXY` list.
+///
+/// Entries match the host itself and its subdomains, `*` matches every host.
+/// IP addresses only match exactly, subnets aren't supported.
+fn is_no_proxy_host(no_proxy: &str, host: &str) -> bool {
+    no_proxy.split(',').map(str::trim).any(|entry| {
+        let entry = entry.trim_start_matches('.');
+        entry == "*"
+            || (!entry.is_empty()
+                && (host.eq_ignore_ascii_case(entry)
+                    || host
+                        .to_ascii_lowercase()
+                        .ends_with(&format!(".{}", entry.to_ascii_lowercase()))))
+    })
+}
+
+/// Removes `user:pass@` from the URL, so it doesn't leak into logs and errors.
+fn take_credentials(url: &mut Url) -> Option<(String, Option<String>)> {
+    if url.username().is_empty() {
+        return None;
+    }
+    let username = percent_decode_str(url.username())
+        .decode_utf8_lossy()
+        .into_owned();
+    let password = url
+        .password()
+        .map(|p| percent_decode_str(p).decode_utf8_lossy().into_owned());
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    Some((username, password))
+}
+
+fn header_map(headers: &[(String, String)]) -> Result<HeaderMap> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_str(name).map_err(|e| {
+                SnapshotError::InvalidSource(format!("bad HTTP header name {:?}: {}", name, e))
+            })?;
+            let mut value = HeaderValue::from_str(value).map_err(|e| {
+                SnapshotError::InvalidSource(format!("bad HTTP header value for {}: {}", name, e))
+            })?;
+            // Custom headers usually carry tokens, keep them out of debug output.
+            value.set_sensitive(true);
+            Ok((name, value))
+        })
+        .collect()
+}
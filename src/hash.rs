@@ -0,0 +1,18 @@
+//! Fingerprints of account data.
+//!
+//! BLAKE3 is used by default. Enable the `sha256_hash` feature to use SHA-256 instead.
+
+/// Size of an account data digest in bytes.
+pub const HASH_BYTES: usize = 32;
+
+/// Returns the digest of the given account data.
+#[cfg(not(feature = "sha256_hash"))]
+pub fn account_data_hash(data: &[u8]) -> [u8; HASH_BYTES] {
+    *blake3::hash(data).as_bytes()
+}
+
+/// Returns the digest of the given account data.
+#[cfg(feature = "sha256_hash")]
+pub fn account_data_hash(data: &[u8]) -> [u8; HASH_BYTES] {
+    solana_sdk::hash::hash(data).to_bytes()
+}
@@ -1,17 +1,46 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::hash::{extend_and_hash, hashv, Hash};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
 use std::cell::RefCell;
+use std::collections::hash_map::{self, Entry};
+use std::collections::HashMap;
 use std::ffi::OsStr;
+use std::fmt;
 use std::io::Read;
+use std::iter::FusedIterator;
 use std::path::Path;
 use std::rc::Rc;
-use std::str::FromStr;
 use thiserror::Error;
 
 pub mod append_vec;
+pub mod bincode_stream;
+#[cfg(feature = "blake3")]
+pub mod bloom;
+pub mod classify;
+pub mod compression;
+#[cfg(any(feature = "blake3", feature = "sha256_hash"))]
+pub mod hash;
+pub mod seekable;
 pub mod solana;
+pub mod status_cache;
+pub mod sysvars;
 
 pub mod archived;
+#[cfg(feature = "async")]
+pub mod async_archived;
+pub mod loader;
 pub mod unpacked;
 
+#[cfg(feature = "http")]
+mod download;
+#[cfg(feature = "s3")]
+pub mod s3;
+
+#[cfg(feature = "rbpf")]
+pub mod opcode_stats;
 #[cfg(feature = "parallel")]
 pub mod parallel;
 
@@ -20,6 +49,7 @@ use crate::solana::{
     deserialize_from, AccountsDbFields, DeserializableVersionedBank,
     SerializableAccountStorageEntry,
 };
+use crate::sysvars::SnapshotSysvars;
 
 const SNAPSHOTS_DIR: &str = "snapshots";
 
@@ -35,6 +65,30 @@ pub enum SnapshotError {
     NoSnapshotManifest,
     #[error("Unexpected AppendVec")]
     UnexpectedAppendVec,
+    #[error(
+        "AppendVec {slot}.{id} is truncated: {available} bytes available, manifest declares {len}"
+    )]
+    TruncatedAppendVec {
+        slot: u64,
+        id: u64,
+        available: u64,
+        len: usize,
+    },
+    #[error("Failed to read archive member {path}: {source}")]
+    ArchiveMemberError {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("Invalid snapshot source: {0}")]
+    InvalidSource(String),
+    #[error("{0}")]
+    UnsupportedSource(&'static str),
+    #[cfg(feature = "http")]
+    #[error("HTTP request failed: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[cfg(feature = "s3")]
+    #[error("{0}")]
+    S3Error(String),
 }
 
 pub type Result<T> = std::result::Result<T, SnapshotError>;
@@ -45,18 +99,94 @@ pub trait SnapshotExtractor: Sized {
     fn iter(&mut self) -> AppendVecIterator<'_>;
 }
 
-fn parse_append_vec_name(name: &OsStr) -> Option<(u64, u64)> {
+/// Compares the bytes available for an AppendVec against its length in the manifest.
+///
+/// Returns the number of bytes to read. A truncated AppendVec is an error if `strict` is set,
+/// otherwise only the available bytes are read so that no garbage accounts are emitted.
+pub(crate) fn checked_append_vec_len(
+    slot: u64,
+    id: u64,
+    available: u64,
+    len: usize,
+    strict: bool,
+) -> Result<usize> {
+    if available >= len as u64 {
+        return Ok(len);
+    }
+    let err = SnapshotError::TruncatedAppendVec {
+        slot,
+        id,
+        available,
+        len,
+    };
+    if strict {
+        return Err(err);
+    }
+    warn!("{}, reading available bytes only", err);
+    Ok(available as usize)
+}
+
+/// Parses an AppendVec file name into its slot and ID.
+///
+/// The two numbers are usually separated by a dot (`<slot>.<id>`), but `_` and `-` are accepted too,
+/// as are trailing extensions.
+///
+/// ```
+/// use solana_snapshot_etl::parse_append_vec_name;
+///
+/// assert_eq!(parse_append_vec_name("123.456".as_ref()), Some((123, 456)));
+/// assert_eq!(parse_append_vec_name("123.456.tmp".as_ref()), Some((123, 456)));
+/// assert_eq!(parse_append_vec_name("123_456".as_ref()), Some((123, 456)));
+/// assert_eq!(parse_append_vec_name("123".as_ref()), None);
+/// assert_eq!(parse_append_vec_name("123.abc".as_ref()), None);
+/// assert_eq!(parse_append_vec_name("123.456abc".as_ref()), None);
+/// assert_eq!(parse_append_vec_name(".123.456".as_ref()), None);
+/// ```
+pub fn parse_append_vec_name(name: &OsStr) -> Option<(u64, u64)> {
     let name = name.to_str()?;
-    let mut parts = name.splitn(2, '.');
-    let slot = u64::from_str(parts.next().unwrap_or(""));
-    let id = u64::from_str(parts.next().unwrap_or(""));
-    match (slot, id) {
-        (Ok(slot), Ok(version)) => Some((slot, version)),
-        _ => None,
+    let (slot, rest) = split_number(name)?;
+    let (id, rest) = split_number(rest.strip_prefix(['.', '_', '-'])?)?;
+    if !rest.is_empty() && !rest.starts_with('.') {
+        return None;
     }
+    Some((slot, id))
 }
 
-pub fn append_vec_iter(append_vec: Rc<AppendVec>) -> impl Iterator<Item = StoredAccountMetaHandle> {
+/// Splits leading decimal digits off a string.
+fn split_number(s: &str) -> Option<(u64, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, rest) = s.split_at(end);
+    Some((digits.parse().ok()?, rest))
+}
+
+/// Parses the slot and ID of an archive member, warning about skipped files in `accounts/`.
+fn parse_append_vec_path(path: &Path) -> Option<(u64, u64)> {
+    let name = path.file_name()?;
+    if path.parent() == Some(Path::new("accounts")) {
+        parse_accounts_file_name(name)
+    } else {
+        parse_append_vec_name(name)
+    }
+}
+
+/// Like [`parse_append_vec_name`], for files in the accounts directory, warning about skipped ones.
+fn parse_accounts_file_name(name: &OsStr) -> Option<(u64, u64)> {
+    let parsed = parse_append_vec_name(name);
+    if parsed.is_none() {
+        warn!(
+            "Skipping {:?} in accounts directory, not an AppendVec name",
+            name
+        );
+    }
+    parsed
+}
+
+/// Iterates over the readable accounts of an AppendVec.
+///
+/// Account offsets are located up front, so the iterator reports its exact length.
+pub fn append_vec_iter(
+    append_vec: Rc<AppendVec>,
+) -> impl ExactSizeIterator<Item = StoredAccountMetaHandle> + FusedIterator {
     let mut offsets = Vec::<usize>::new();
     let mut offset = 0usize;
     loop {
@@ -68,12 +198,180 @@ pub fn append_vec_iter(append_vec: Rc<AppendVec>) -> impl Iterator<Item = Stored
             }
         }
     }
+    warn_unreadable_tail(&append_vec, offset);
     let append_vec = Rc::clone(&append_vec);
     offsets
         .into_iter()
         .map(move |offset| StoredAccountMetaHandle::new(Rc::clone(&append_vec), offset))
 }
 
+/// Like [`append_vec_iter`], but borrows the AppendVec and yields its accounts directly.
+///
+/// Accounts are parsed as they are visited, so the warning about an unreadable
+/// tail is only logged once the iterator reaches it.
+pub fn append_vec_accounts(
+    append_vec: &AppendVec,
+) -> impl FusedIterator<Item = StoredAccountMeta<'_>> {
+    let mut offset = 0usize;
+    std::iter::from_fn(move || match append_vec.get_account(offset) {
+        Some((account, next_offset)) => {
+            offset = next_offset;
+            Some(account)
+        }
+        None => {
+            warn_unreadable_tail(append_vec, offset);
+            None
+        }
+    })
+    .fuse()
+}
+
+/// Warns if accounts of the AppendVec could only be parsed up to `offset`.
+fn warn_unreadable_tail(append_vec: &AppendVec, offset: usize) {
+    if offset < append_vec.len() {
+        // Account sizes are only known by parsing, so nothing after a corrupt account is readable.
+        warn!(
+            "AppendVec {}.{} (len {}): skipping unreadable accounts from offset {}",
+            append_vec.slot(),
+            append_vec.id(),
+            append_vec.len(),
+            offset
+        );
+    }
+}
+
+/// Calls `f` for every account in the snapshot, stopping at the first error.
+///
+/// The [`StoredAccountMeta`] borrows from its AppendVec, which is dropped after it has been
+/// visited, so it must not escape the closure. Use [`OwnedAccount::from`] to keep a copy.
+pub fn for_each_account<F>(extractor: &mut impl SnapshotExtractor, mut f: F) -> Result<()>
+where
+    F: FnMut(&StoredAccountMeta) -> Result<()>,
+{
+    for append_vec in extractor.iter() {
+        for account in append_vec_accounts(&append_vec?) {
+            f(&account)?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the latest version (highest write version) of an account.
+///
+/// Snapshots are not indexed by pubkey, so this reads the whole snapshot.
+pub fn find_account(
+    extractor: &mut impl SnapshotExtractor,
+    pubkey: &Pubkey,
+) -> Result<Option<OwnedAccount>> {
+    let mut versions = Vec::new();
+    for_each_account(extractor, |account| {
+        if account.meta.pubkey == *pubkey {
+            versions.push(OwnedAccount::from(account));
+        }
+        Ok(())
+    })?;
+    Ok(dedup_latest(versions).next())
+}
+
+/// Keeps the version of each account with the highest write version.
+///
+/// `T` is whatever the caller needs to remember about an account,
+/// so compact summaries can be deduplicated without copying account data.
+pub struct LatestVersions<T> {
+    accounts: HashMap<Pubkey, (u64, T)>,
+}
+
+impl<T> Default for LatestVersions<T> {
+    fn default() -> Self {
+        Self {
+            accounts: HashMap::new(),
+        }
+    }
+}
+
+impl<T> LatestVersions<T> {
+    /// Stores `value` unless a version with a higher write version was already inserted.
+    pub fn insert(&mut self, pubkey: Pubkey, write_version: u64, value: T) {
+        match self.accounts.entry(pubkey) {
+            Entry::Vacant(e) => {
+                e.insert((write_version, value));
+            }
+            Entry::Occupied(mut e) => {
+                if e.get().0 < write_version {
+                    e.insert((write_version, value));
+                }
+            }
+        }
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<&T> {
+        self.accounts.get(pubkey).map(|(_, value)| value)
+    }
+
+    pub fn remove(&mut self, pubkey: &Pubkey) -> Option<T> {
+        self.accounts.remove(pubkey).map(|(_, value)| value)
+    }
+
+    /// Keeps only the accounts for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&Pubkey, &T) -> bool) {
+        self.accounts.retain(|pubkey, (_, value)| f(pubkey, value));
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+}
+
+impl<T> IntoIterator for LatestVersions<T> {
+    type Item = (Pubkey, T);
+    type IntoIter =
+        std::iter::Map<hash_map::IntoIter<Pubkey, (u64, T)>, fn((Pubkey, (u64, T))) -> (Pubkey, T)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.accounts
+            .into_iter()
+            .map(|(pubkey, (_, value))| (pubkey, value))
+    }
+}
+
+/// Collapses account versions with the same pubkey, keeping the highest write version.
+///
+/// All accounts are buffered in memory before the first one is yielded, in no particular order.
+/// If the input is already sorted by pubkey, grouping adjacent items
+/// (e.g. with `itertools::Itertools::group_by`) avoids the buffering.
+///
+/// ```
+/// use solana_sdk::pubkey::Pubkey;
+/// use solana_snapshot_etl::{dedup_latest, OwnedAccount};
+///
+/// let pubkey = Pubkey::new_unique();
+/// let version = |write_version, lamports| OwnedAccount {
+///     pubkey,
+///     owner: Pubkey::default(),
+///     lamports,
+///     data: vec![],
+///     executable: false,
+///     rent_epoch: 0,
+///     write_version,
+/// };
+/// let latest: Vec<_> = dedup_latest([version(1, 10), version(3, 30), version(2, 20)]).collect();
+/// assert_eq!(latest, [version(3, 30)]);
+/// ```
+pub fn dedup_latest<I>(accounts: I) -> impl Iterator<Item = OwnedAccount>
+where
+    I: IntoIterator<Item = OwnedAccount>,
+{
+    let mut latest = LatestVersions::default();
+    for account in accounts {
+        latest.insert(account.pubkey, account.write_version, account);
+    }
+    latest.into_iter().map(|(_, account)| account)
+}
+
 pub struct StoredAccountMetaHandle {
     append_vec: Rc<AppendVec>,
     offset: usize,
@@ -87,9 +385,173 @@ impl StoredAccountMetaHandle {
     pub fn access(&self) -> Option<StoredAccountMeta<'_>> {
         Some(self.append_vec.get_account(self.offset)?.0)
     }
+
+    /// Copies the account out of the AppendVec.
+    pub fn to_owned_account(&self) -> Option<OwnedAccount> {
+        self.access().map(|account| OwnedAccount::from(&account))
+    }
+}
+
+/// Shows the account's location and, if it can be read, its pubkey, owner, lamports,
+/// and data length. The data itself is left out to keep logs short.
+impl fmt::Debug for StoredAccountMetaHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("StoredAccountMetaHandle");
+        debug
+            .field("slot", &self.append_vec.slot())
+            .field("id", &self.append_vec.id())
+            .field("offset", &self.offset);
+        match self.access() {
+            Some(account) => debug
+                .field("pubkey", &account.meta.pubkey)
+                .field("owner", &account.account_meta.owner)
+                .field("lamports", &account.account_meta.lamports)
+                .field("data_len", &account.meta.data_len)
+                .finish(),
+            None => debug.finish_non_exhaustive(),
+        }
+    }
+}
+
+/// Formats as e.g. `account <pubkey> in AppendVec 15.5 at offset 880`, for error messages.
+impl fmt::Display for StoredAccountMetaHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.access() {
+            Some(account) => write!(f, "account {} in ", account.meta.pubkey)?,
+            None => write!(f, "unreadable account in ")?,
+        }
+        write!(
+            f,
+            "AppendVec {}.{} at offset {}",
+            self.append_vec.slot(),
+            self.append_vec.id(),
+            self.offset
+        )
+    }
+}
+
+/// An account copied out of an AppendVec.
+///
+/// Unlike [`StoredAccountMeta`], this does not borrow from the backing AppendVec,
+/// at the cost of copying the account data.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnedAccount {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub write_version: u64,
+}
+
+impl<'a> From<&StoredAccountMeta<'a>> for OwnedAccount {
+    fn from(account: &StoredAccountMeta<'a>) -> Self {
+        Self {
+            pubkey: account.meta.pubkey,
+            owner: account.account_meta.owner,
+            lamports: account.account_meta.lamports,
+            data: account.data.to_vec(),
+            executable: account.account_meta.executable,
+            rent_epoch: account.account_meta.rent_epoch,
+            write_version: account.meta.write_version,
+        }
+    }
+}
+
+/// Bank fields kept from the snapshot manifest.
+#[derive(Clone, Debug)]
+pub struct BankSummary {
+    pub slot: Slot,
+    pub epoch: Epoch,
+    pub rent: Rent,
+    /// Bank hash advertised by the snapshot.
+    pub hash: Hash,
+    pub parent_hash: Hash,
+    pub parent_slot: Slot,
+    pub signature_count: u64,
+    pub last_blockhash: Hash,
+    /// Total lamports in existence.
+    pub capitalization: u64,
+    /// Extra data mixed into the bank hash if the bank follows a hard fork.
+    pub hard_fork_data: Option<[u8; 8]>,
+    /// Hash of the accounts written in this slot.
+    pub accounts_delta_hash: Hash,
+    /// Hash of all accounts at this slot.
+    pub accounts_hash: Hash,
+    /// Total length of all AppendVecs listed in the manifest.
+    pub append_vecs_len: u64,
+    pub sysvars: SnapshotSysvars,
+}
+
+impl From<&DeserializableVersionedBank> for BankSummary {
+    fn from(bank: &DeserializableVersionedBank) -> Self {
+        Self {
+            slot: bank.slot,
+            epoch: bank.epoch,
+            rent: bank.rent_collector.rent,
+            hash: bank.hash,
+            parent_hash: bank.parent_hash,
+            parent_slot: bank.parent_slot,
+            signature_count: bank.signature_count,
+            last_blockhash: bank.blockhash_queue.last_hash(),
+            capitalization: bank.capitalization,
+            hard_fork_data: bank.hard_forks.get_hash_data(bank.slot, bank.parent_slot),
+            accounts_delta_hash: Hash::default(),
+            accounts_hash: Hash::default(),
+            append_vecs_len: 0,
+            sysvars: SnapshotSysvars::from(bank),
+        }
+    }
+}
+
+impl BankSummary {
+    /// Copies the fields that are stored in the accounts DB fields.
+    pub(crate) fn set_accounts_db_fields(
+        &mut self,
+        fields: &AccountsDbFields<SerializableAccountStorageEntry>,
+    ) {
+        self.accounts_delta_hash = fields.3.hash;
+        self.accounts_hash = fields.3.snapshot_hash;
+        self.append_vecs_len = fields
+            .0
+            .values()
+            .flatten()
+            .map(|entry| entry.accounts_current_len as u64)
+            .sum();
+    }
+
+    /// Computes the bank hash the same way the validator does when freezing a bank.
+    pub fn compute_bank_hash(&self, accounts_delta_hash: &Hash) -> Hash {
+        let hash = hashv(&[
+            self.parent_hash.as_ref(),
+            accounts_delta_hash.as_ref(),
+            &self.signature_count.to_le_bytes(),
+            self.last_blockhash.as_ref(),
+        ]);
+        match &self.hard_fork_data {
+            Some(data) => extend_and_hash(&hash, data),
+            None => hash,
+        }
+    }
+}
+
+/// Returns whether an account is exempt from rent.
+///
+/// This is the case if it is marked with `rent_epoch == u64::MAX`,
+/// or if it holds at least the rent-exempt minimum balance for its data size.
+pub fn is_rent_exempt(rent: &Rent, account: &StoredAccountMeta) -> bool {
+    account.account_meta.rent_epoch == u64::MAX
+        || rent.is_exempt(
+            account.account_meta.lamports,
+            account.meta.data_len as usize,
+        )
 }
 
 pub trait ReadProgressTracking {
+    /// Wraps a reader to track how many bytes were read.
+    ///
+    /// `file_len` is zero if the total length is not known upfront.
     fn new_read_progress_tracker(
         &self,
         path: &Path,
@@ -98,7 +560,8 @@ pub trait ReadProgressTracking {
     ) -> Box<dyn Read>;
 }
 
-struct NullReadProgressTracking {}
+/// Does not track reads.
+pub struct NullReadProgressTracking {}
 
 impl ReadProgressTracking for NullReadProgressTracking {
     fn new_read_progress_tracker(&self, _: &Path, rd: Box<dyn Read>, _: u64) -> Box<dyn Read> {
@@ -123,3 +586,78 @@ impl<T: Read> Read for RefCellRead<T> {
             .and_then(|mut rd| rd.read(buf))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts(n: u8) -> Vec<OwnedAccount> {
+        (0..n)
+            .map(|i| OwnedAccount {
+                pubkey: Pubkey::new_unique(),
+                owner: Pubkey::new_unique(),
+                lamports: i as u64,
+                data: vec![i; i as usize * 10],
+                executable: false,
+                rent_epoch: 0,
+                write_version: i as u64,
+            })
+            .collect()
+    }
+
+    /// Lays out the accounts in an AppendVec, then cuts `cut` bytes off its end.
+    fn truncated_append_vec(accounts: &[OwnedAccount], cut: usize) -> AppendVec {
+        let append_vec = AppendVec::new_from_accounts(accounts, 0, 0).unwrap();
+        let bytes = append_vec.as_bytes();
+        let len = bytes.len() - cut;
+        AppendVec::new_from_reader(&mut &bytes[..len], len, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn truncated_tail_is_skipped() {
+        let accounts = accounts(3);
+        // Cut into the data of the last account.
+        let append_vec = Rc::new(truncated_append_vec(&accounts, 8));
+
+        let pubkeys: Vec<Pubkey> = append_vec_iter(Rc::clone(&append_vec))
+            .map(|handle| handle.access().unwrap().meta.pubkey)
+            .collect();
+        assert_eq!(pubkeys, [accounts[0].pubkey, accounts[1].pubkey]);
+
+        let owned: Vec<OwnedAccount> = append_vec_accounts(&append_vec)
+            .map(|account| OwnedAccount::from(&account))
+            .collect();
+        assert_eq!(owned, accounts[..2]);
+    }
+
+    #[test]
+    fn append_vec_iter_len_matches_yielded_count() {
+        let append_vec = Rc::new(truncated_append_vec(&accounts(5), 0));
+        let mut iter = append_vec_iter(Rc::clone(&append_vec));
+        assert_eq!(iter.len(), 5);
+        assert_eq!(iter.size_hint(), (5, Some(5)));
+        iter.next().unwrap();
+        iter.next().unwrap();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.by_ref().count(), 3);
+        assert_eq!(iter.len(), 0);
+        assert!(iter.next().is_none());
+
+        // Accounts in an unreadable tail are not counted.
+        let append_vec = Rc::new(truncated_append_vec(&accounts(5), 8));
+        let iter = append_vec_iter(append_vec);
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.count(), 4);
+    }
+
+    #[test]
+    fn intact_append_vec_yields_all_accounts() {
+        let accounts = accounts(3);
+        let append_vec = truncated_append_vec(&accounts, 0);
+        let owned: Vec<OwnedAccount> = append_vec_accounts(&append_vec)
+            .map(|account| OwnedAccount::from(&account))
+            .collect();
+        assert_eq!(owned, accounts);
+    }
+}
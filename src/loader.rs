@@ -0,0 +1,366 @@
+//! Opens snapshots from any of the supported sources.
+
+use crate::append_vec::DEFAULT_MAX_DATA_LEN;
+use crate::archived::{ArchiveSnapshotExtractor, StreamCounters};
+#[cfg(feature = "s3")]
+use crate::s3::S3Reader;
+use crate::status_cache::StatusCacheEntry;
+use crate::sysvars::SnapshotSysvars;
+use crate::unpacked::UnpackedSnapshotExtractor;
+use crate::{
+    AppendVec, AppendVecIterator, BankSummary, NullReadProgressTracking, ReadProgressTracking,
+    Result, SnapshotExtractor,
+};
+use log::info;
+#[cfg(feature = "http")]
+use reqwest::blocking::Response;
+use solana_sdk::clock::Slot;
+use std::fs::File;
+use std::io::{Read, Stdin};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::time::Duration;
+
+/// Options for streaming snapshots over HTTP.
+#[derive(Clone, Debug)]
+pub struct HttpOptions {
+    /// Timeout for connecting and for each read from the connection.
+    pub timeout: Duration,
+    /// Number of times a failed request is retried.
+    pub retries: u32,
+    /// Extra headers sent with each request.
+    pub headers: Vec<(String, String)>,
+    /// Proxy URL for all requests, e.g. `http://proxy:3128` or `socks5h://proxy:1080`.
+    ///
+    /// Without it, the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`, and `NO_PROXY`
+    /// environment variables are honored.
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            retries: 3,
+            headers: Vec::new(),
+            proxy: None,
+        }
+    }
+}
+
+/// Accumulates extractor options, then opens a snapshot with them.
+///
+/// ```no_run
+/// use solana_snapshot_etl::loader::ExtractorBuilder;
+///
+/// let loader = ExtractorBuilder::new()
+///     .slot_range(100..=200)
+///     .strict(true)
+///     .open("snapshot-200-xxx.tar.zst")?;
+/// # Ok::<(), solana_snapshot_etl::SnapshotError>(())
+/// ```
+pub struct ExtractorBuilder {
+    progress_tracking: Box<dyn ReadProgressTracking>,
+    http_options: HttpOptions,
+    slot_range: RangeInclusive<Slot>,
+    strict: bool,
+    max_data_len: u64,
+    num_threads: usize,
+}
+
+impl Default for ExtractorBuilder {
+    fn default() -> Self {
+        Self {
+            progress_tracking: Box::new(NullReadProgressTracking {}),
+            http_options: HttpOptions::default(),
+            slot_range: 0..=Slot::MAX,
+            strict: false,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
+            num_threads: 1,
+        }
+    }
+}
+
+impl ExtractorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Tracks reads of snapshot files. Only used for unpacked snapshots.
+    pub fn progress_tracking(mut self, progress_tracking: Box<dyn ReadProgressTracking>) -> Self {
+        self.progress_tracking = progress_tracking;
+        self
+    }
+
+    pub fn http_options(mut self, http_options: HttpOptions) -> Self {
+        self.http_options = http_options;
+        self
+    }
+
+    /// Only yields AppendVecs whose slot is within the range.
+    pub fn slot_range(mut self, slot_range: RangeInclusive<Slot>) -> Self {
+        self.slot_range = slot_range;
+        self
+    }
+
+    /// Fails on AppendVecs that are shorter than declared in the manifest, instead of warning.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Stops reading an AppendVec at an account declaring more data than this,
+    /// 10 MiB (the Solana account size limit) by default.
+    pub fn max_data_len(mut self, max_data_len: u64) -> Self {
+        self.max_data_len = max_data_len;
+        self
+    }
+
+    /// Decompresses seekable zstd archive files on this many threads, 1 by default.
+    /// See [`ArchiveSnapshotExtractor::open_parallel`].
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Opens a snapshot, picking the extractor based on the source string.
+    ///
+    /// The source is an unpacked snapshot directory, an archive file, an HTTP link, an S3 URL,
+    /// or `-` for a .tar.zst stream on stdin.
+    pub fn open(self, source: &str) -> Result<SupportedLoader> {
+        let mut loader = if source == "-" {
+            SupportedLoader::new_stdin()?
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            SupportedLoader::new_download(source, &self.http_options)?
+        } else if source.starts_with("s3://") {
+            SupportedLoader::new_s3(source)?
+        } else {
+            SupportedLoader::open_file(source.as_ref(), self.progress_tracking, self.num_threads)?
+        };
+        loader.set_slot_range(self.slot_range);
+        loader.set_strict(self.strict);
+        loader.set_max_data_len(self.max_data_len);
+        Ok(loader)
+    }
+
+    /// Reads a .tar.zst stream.
+    pub fn from_reader<Source>(self, source: Source) -> Result<ArchiveSnapshotExtractor<Source>>
+    where
+        Source: Read + Unpin + 'static,
+    {
+        let mut loader = ArchiveSnapshotExtractor::from_reader(source)?;
+        loader.set_slot_range(self.slot_range);
+        loader.set_strict(self.strict);
+        loader.set_max_data_len(self.max_data_len);
+        Ok(loader)
+    }
+}
+
+/// Extractor for unpacked snapshots, archive files, stdin, HTTP links, and S3 URLs.
+pub enum SupportedLoader {
+    Unpacked(UnpackedSnapshotExtractor),
+    ArchiveFile(ArchiveSnapshotExtractor<File>),
+    ArchiveStdin(ArchiveSnapshotExtractor<Stdin>),
+    #[cfg(feature = "http")]
+    ArchiveDownload(ArchiveSnapshotExtractor<Response>),
+    #[cfg(feature = "s3")]
+    ArchiveS3(ArchiveSnapshotExtractor<S3Reader>),
+}
+
+impl SupportedLoader {
+    /// Opens a snapshot, picking the extractor based on the source string.
+    ///
+    /// Seekable zstd archive files are decompressed on `num_threads` threads.
+    pub fn new(
+        source: &str,
+        progress_tracking: Box<dyn ReadProgressTracking>,
+        http_options: &HttpOptions,
+        num_threads: usize,
+    ) -> Result<Self> {
+        ExtractorBuilder::new()
+            .progress_tracking(progress_tracking)
+            .http_options(http_options.clone())
+            .num_threads(num_threads)
+            .open(source)
+    }
+
+    /// Reads a .tar.zst stream from stdin.
+    pub fn new_stdin() -> Result<Self> {
+        let loader = ArchiveSnapshotExtractor::from_reader_threaded(std::io::stdin())?;
+        info!("Reading snapshot archive from stdin");
+        Ok(Self::ArchiveStdin(loader))
+    }
+
+    #[cfg(feature = "http")]
+    pub fn new_download(url: &str, http_options: &HttpOptions) -> Result<Self> {
+        let resp = crate::download::get(url, http_options)?;
+        let loader = ArchiveSnapshotExtractor::from_reader_threaded(resp)?;
+        info!("Streaming snapshot from HTTP");
+        Ok(Self::ArchiveDownload(loader))
+    }
+
+    #[cfg(not(feature = "http"))]
+    pub fn new_download(_: &str, _: &HttpOptions) -> Result<Self> {
+        Err(crate::SnapshotError::UnsupportedSource(
+            "HTTP support is not enabled, rebuild with the http feature",
+        ))
+    }
+
+    #[cfg(feature = "s3")]
+    pub fn new_s3(url: &str) -> Result<Self> {
+        let rd = S3Reader::open(url)?;
+        let loader = ArchiveSnapshotExtractor::from_reader_threaded(rd)?;
+        info!("Streaming snapshot from S3");
+        Ok(Self::ArchiveS3(loader))
+    }
+
+    #[cfg(not(feature = "s3"))]
+    pub fn new_s3(_: &str) -> Result<Self> {
+        Err(crate::SnapshotError::UnsupportedSource(
+            "S3 support is not enabled, rebuild with the s3 feature",
+        ))
+    }
+
+    pub fn new_file(path: &Path, progress_tracking: Box<dyn ReadProgressTracking>) -> Result<Self> {
+        Self::open_file(path, progress_tracking, 1)
+    }
+
+    fn open_file(
+        path: &Path,
+        progress_tracking: Box<dyn ReadProgressTracking>,
+        num_threads: usize,
+    ) -> Result<Self> {
+        Ok(if path.is_dir() {
+            info!("Reading unpacked snapshot");
+            Self::Unpacked(UnpackedSnapshotExtractor::open(path, progress_tracking)?)
+        } else {
+            info!("Reading snapshot archive");
+            Self::ArchiveFile(ArchiveSnapshotExtractor::open_parallel(path, num_threads)?)
+        })
+    }
+
+    /// Only yields AppendVecs whose slot is within the range.
+    pub fn set_slot_range(&mut self, slot_range: RangeInclusive<Slot>) {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.set_slot_range(slot_range),
+            SupportedLoader::ArchiveFile(loader) => loader.set_slot_range(slot_range),
+            SupportedLoader::ArchiveStdin(loader) => loader.set_slot_range(slot_range),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => loader.set_slot_range(slot_range),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => loader.set_slot_range(slot_range),
+        }
+    }
+
+    /// Fails on AppendVecs that are shorter than declared in the manifest, instead of warning.
+    pub fn set_strict(&mut self, strict: bool) {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.set_strict(strict),
+            SupportedLoader::ArchiveFile(loader) => loader.set_strict(strict),
+            SupportedLoader::ArchiveStdin(loader) => loader.set_strict(strict),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => loader.set_strict(strict),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => loader.set_strict(strict),
+        }
+    }
+
+    /// Sets the largest `data_len` accepted from accounts, see [`AppendVec::set_max_data_len`].
+    pub fn set_max_data_len(&mut self, max_data_len: u64) {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.set_max_data_len(max_data_len),
+            SupportedLoader::ArchiveFile(loader) => loader.set_max_data_len(max_data_len),
+            SupportedLoader::ArchiveStdin(loader) => loader.set_max_data_len(max_data_len),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => loader.set_max_data_len(max_data_len),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => loader.set_max_data_len(max_data_len),
+        }
+    }
+
+    /// Byte counters of the archive stream, `None` for unpacked snapshots.
+    pub fn stream_counters(&self) -> Option<StreamCounters> {
+        match self {
+            SupportedLoader::Unpacked(_) => None,
+            SupportedLoader::ArchiveFile(loader) => Some(loader.stream_counters()),
+            SupportedLoader::ArchiveStdin(loader) => Some(loader.stream_counters()),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => Some(loader.stream_counters()),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => Some(loader.stream_counters()),
+        }
+    }
+
+    /// Reads the status cache, call this before iterating AppendVecs.
+    pub fn status_cache(&mut self) -> Result<Vec<StatusCacheEntry>> {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.status_cache(),
+            SupportedLoader::ArchiveFile(loader) => loader.status_cache(),
+            SupportedLoader::ArchiveStdin(loader) => loader.status_cache(),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => loader.status_cache(),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => loader.status_cache(),
+        }
+    }
+
+    /// Bank fields from the snapshot manifest.
+    pub fn bank(&self) -> &BankSummary {
+        match self {
+            SupportedLoader::Unpacked(loader) => loader.bank(),
+            SupportedLoader::ArchiveFile(loader) => loader.bank(),
+            SupportedLoader::ArchiveStdin(loader) => loader.bank(),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => loader.bank(),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => loader.bank(),
+        }
+    }
+
+    /// Epoch schedule, rent, and fee parameters from the snapshot manifest.
+    pub fn sysvars(&self) -> &SnapshotSysvars {
+        &self.bank().sysvars
+    }
+}
+
+/// Consumes the loader, yielding its AppendVecs.
+///
+/// ```no_run
+/// use solana_snapshot_etl::loader::ExtractorBuilder;
+///
+/// let loader = ExtractorBuilder::new().open("snapshot-200-xxx.tar.zst")?;
+/// for append_vec in loader {
+///     println!("slot {}", append_vec?.slot());
+/// }
+/// # Ok::<(), solana_snapshot_etl::SnapshotError>(())
+/// ```
+impl IntoIterator for SupportedLoader {
+    type Item = Result<AppendVec>;
+    type IntoIter = AppendVecIterator<'static>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            SupportedLoader::Unpacked(loader) => Box::new(loader.into_iter()),
+            SupportedLoader::ArchiveFile(loader) => Box::new(loader.into_iter()),
+            SupportedLoader::ArchiveStdin(loader) => Box::new(loader.into_iter()),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => Box::new(loader.into_iter()),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => Box::new(loader.into_iter()),
+        }
+    }
+}
+
+impl SnapshotExtractor for SupportedLoader {
+    fn iter(&mut self) -> AppendVecIterator<'_> {
+        match self {
+            SupportedLoader::Unpacked(loader) => Box::new(loader.iter()),
+            SupportedLoader::ArchiveFile(loader) => Box::new(loader.iter()),
+            SupportedLoader::ArchiveStdin(loader) => Box::new(loader.iter()),
+            #[cfg(feature = "http")]
+            SupportedLoader::ArchiveDownload(loader) => Box::new(loader.iter()),
+            #[cfg(feature = "s3")]
+            SupportedLoader::ArchiveS3(loader) => Box::new(loader.iter()),
+        }
+    }
+}
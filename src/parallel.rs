@@ -1,5 +1,9 @@
-use crate::{AppendVec, AppendVecIterator};
+use crate::{append_vec_iter, AppendVec, AppendVecIterator, OwnedAccount, SnapshotExtractor};
 use crossbeam::sync::WaitGroup;
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::iter::{IterBridge, ParallelBridge, ParallelIterator};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 pub type GenericResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
@@ -19,6 +23,41 @@ pub fn par_iter_append_vecs<A>(
 ) -> GenericResult<()>
 where
     A: AppendVecConsumerFactory,
+{
+    par_iter_append_vecs_with_callback(iterator, consumers, num_threads, |_, _, _| {})
+}
+
+/// Like [`par_iter_append_vecs`], but calls `on_complete(slot, id, account_count)`
+/// after a worker has consumed an AppendVec.
+///
+/// Calls are serialized, so the callback may keep state such as running totals
+/// without further synchronization.
+///
+/// ```no_run
+/// # use solana_snapshot_etl::parallel::{AppendVecConsumerFactory, GenericResult};
+/// # fn example<A: AppendVecConsumerFactory>(consumers: &mut A) -> GenericResult<()> {
+/// use solana_snapshot_etl::loader::ExtractorBuilder;
+/// use solana_snapshot_etl::parallel::par_iter_append_vecs_with_callback;
+/// use solana_snapshot_etl::SnapshotExtractor;
+///
+/// let mut loader = ExtractorBuilder::new().open("snapshot-200-xxx.tar.zst")?;
+/// let mut accounts_done = 0;
+/// par_iter_append_vecs_with_callback(loader.iter(), consumers, 8, move |slot, id, count| {
+///     accounts_done += count;
+///     println!("AppendVec {}.{} done, {} accounts so far", slot, id, accounts_done);
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn par_iter_append_vecs_with_callback<A, F>(
+    iterator: AppendVecIterator<'_>,
+    consumers: &mut A,
+    num_threads: usize,
+    on_complete: F,
+) -> GenericResult<()>
+where
+    A: AppendVecConsumerFactory,
+    F: FnMut(u64, u64, usize) + Send + 'static,
 {
     let (tx, rx) = crossbeam::channel::bounded::<AppendVec>(num_threads);
 
@@ -28,13 +67,21 @@ where
         consumer_vec.push(consumers.new_consumer()?);
     }
 
+    let on_complete = Arc::new(Mutex::new(on_complete));
     for mut consumer in consumer_vec {
         let rx = rx.clone();
         let wg = wg.clone();
+        let on_complete = Arc::clone(&on_complete);
         std::thread::spawn(move || {
             while let Ok(item) = rx.recv() {
-                consumer.on_append_vec(item).expect("insert failed")
+                let (slot, id) = (item.slot(), item.id());
+                let account_count = count_accounts(&item);
+                consumer.on_append_vec(item).expect("insert failed");
+                (on_complete.lock().unwrap())(slot, id, account_count);
             }
+            // Release the consumer before signalling, so that state it shares
+            // with the factory is unreferenced once `wg.wait()` returns.
+            drop(consumer);
             drop(wg);
         });
     }
@@ -47,3 +94,87 @@ where
     wg.wait();
     Ok(())
 }
+
+/// Counts the readable accounts of an AppendVec by walking their headers.
+fn count_accounts(append_vec: &AppendVec) -> usize {
+    let mut count = 0;
+    let mut offset = 0;
+    while let Some((_, next_offset)) = append_vec.get_account(offset) {
+        count += 1;
+        offset = next_offset;
+    }
+    count
+}
+
+/// Hands a rayon [`ParallelIterator`] over all accounts to `f`, and returns its result.
+///
+/// AppendVecs are read on the calling thread and distributed across the rayon pool,
+/// where their accounts are copied into [`OwnedAccount`]s so they can be sent between threads.
+/// Stops reading the snapshot once `f` returns, so short-circuiting operations like
+/// `find_any` don't read the rest of it.
+///
+/// ```no_run
+/// use rayon::iter::ParallelIterator;
+/// use solana_snapshot_etl::loader::ExtractorBuilder;
+/// use solana_snapshot_etl::parallel::par_accounts;
+///
+/// let mut loader = ExtractorBuilder::new().open("snapshot-200-xxx.tar.zst")?;
+/// let lamports: u64 = par_accounts(&mut loader, |accounts| accounts.map(|a| a.lamports).sum())?;
+/// # Ok::<(), solana_snapshot_etl::SnapshotError>(())
+/// ```
+pub fn par_accounts<E, F, R>(extractor: &mut E, f: F) -> crate::Result<R>
+where
+    E: SnapshotExtractor,
+    F: FnOnce(ParAccounts) -> R + Send,
+    R: Send,
+{
+    let (tx, rx) = crossbeam::channel::bounded::<AppendVec>(rayon::current_num_threads());
+    std::thread::scope(|scope| {
+        let handle = scope.spawn(move || {
+            f(ParAccounts {
+                append_vecs: rx.into_iter().par_bridge(),
+            })
+        });
+        let mut result = Ok(());
+        for append_vec in extractor.iter() {
+            match append_vec {
+                Ok(append_vec) => {
+                    if tx.send(append_vec).is_err() {
+                        // f returned without consuming all accounts.
+                        break;
+                    }
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        drop(tx);
+        let value = match handle.join() {
+            Ok(value) => value,
+            Err(panic) => std::panic::resume_unwind(panic),
+        };
+        result.map(|_| value)
+    })
+}
+
+/// Parallel iterator over the accounts in a snapshot, see [`par_accounts`].
+pub struct ParAccounts {
+    append_vecs: IterBridge<crossbeam::channel::IntoIter<AppendVec>>,
+}
+
+impl ParallelIterator for ParAccounts {
+    type Item = OwnedAccount;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.append_vecs
+            .flat_map_iter(|append_vec| {
+                append_vec_iter(Rc::new(append_vec)).filter_map(|handle| handle.to_owned_account())
+            })
+            .drive_unindexed(consumer)
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{Result, SnapshotError};
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::primitives::ByteStream;
+use std::io::{IoSliceMut, Read};
+use std::pin::Pin;
+use tokio::io::AsyncRead;
+use tokio::runtime::Runtime;
+use tokio_util::io::SyncIoBridge;
+
+type AsyncBody = Pin<Box<dyn AsyncRead + Send>>;
+
+/// Blocking reader over the body of an S3 object.
+pub struct S3Reader {
+    // Declared before the runtime, as it has to be dropped first.
+    body: SyncIoBridge<AsyncBody>,
+    _runtime: Runtime,
+}
+
+impl S3Reader {
+    /// Requests an object given an `s3://bucket/key` URL.
+    ///
+    /// Credentials are resolved through the standard AWS provider chain
+    /// (environment variables, profile files, IMDS).
+    pub fn open(url: &str) -> Result<Self> {
+        let (bucket, key) = parse_s3_url(url).ok_or_else(|| {
+            SnapshotError::InvalidSource(format!("expected s3://bucket/key, got {}", url))
+        })?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let body: ByteStream = runtime
+            .block_on(async {
+                let config = aws_config::load_from_env().await;
+                let client = aws_sdk_s3::Client::new(&config);
+                client.get_object().bucket(bucket).key(key).send().await
+            })
+            .map_err(|e| SnapshotError::S3Error(describe_get_object_error(e, url)))?
+            .body;
+        let body: AsyncBody = Box::pin(body.into_async_read());
+        Ok(Self {
+            body: SyncIoBridge::new_with_handle(body, runtime.handle().clone()),
+            _runtime: runtime,
+        })
+    }
+}
+
+impl Read for S3Reader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.body.read(buf)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+        self.body.read_vectored(bufs)
+    }
+}
+
+fn parse_s3_url(url: &str) -> Option<(&str, &str)> {
+    let (bucket, key) = url.strip_prefix("s3://")?.split_once('/')?;
+    if bucket.is_empty() || key.is_empty() {
+        return None;
+    }
+    Some((bucket, key))
+}
+
+fn describe_get_object_error<R>(err: SdkError<GetObjectError, R>, url: &str) -> String
+where
+    R: std::fmt::Debug,
+{
+    match &err {
+        SdkError::ServiceError(e) if matches!(e.err(), GetObjectError::NoSuchKey(_)) => {
+            format!("S3 object does not exist: {}", url)
+        }
+        SdkError::ServiceError(e) if e.err().code() == Some("AccessDenied") => format!(
+            "Access denied to S3 object {} (HTTP 403), check credentials and bucket policy",
+            url
+        ),
+        SdkError::ConstructionFailure(_) | SdkError::DispatchFailure(_) => format!(
+            "Failed to request S3 object {} (are AWS credentials configured?): {}",
+            url,
+            DisplayErrorContext(&err)
+        ),
+        _ => format!(
+            "Failed to request S3 object {}: {}",
+            url,
+            DisplayErrorContext(&err)
+        ),
+    }
+}
@@ -0,0 +1,485 @@
+//! Decompression of zstd archives in the seekable format.
+//!
+//! The [seekable format] splits the stream into independent frames and appends a seek table
+//! (in a skippable frame) listing the compressed and decompressed size of each frame.
+//! This allows decompressing the frames in parallel, and from any offset by starting
+//! at the frame that contains it.
+//!
+//! [seekable format]: https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md
+
+use std::collections::VecDeque;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Take};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+
+const SKIPPABLE_MAGIC: u32 = 0x184d2a5e;
+const SEEKABLE_MAGIC: u32 = 0x8f92eab1;
+const FOOTER_SIZE: u64 = 9;
+const SKIPPABLE_HEADER_SIZE: u64 = 8;
+const CHECKSUM_FLAG: u8 = 0x80;
+const RESERVED_BITS: u8 = 0x7c;
+
+/// A zstd frame as listed in the seek table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub compressed_offset: u64,
+    pub compressed_size: u64,
+    pub decompressed_offset: u64,
+    pub decompressed_size: u64,
+}
+
+impl Frame {
+    fn decompressed_end(&self) -> u64 {
+        self.decompressed_offset + self.decompressed_size
+    }
+}
+
+/// Seek table of a zstd seekable stream.
+#[derive(Clone, Debug)]
+pub struct SeekTable {
+    frames: Vec<Frame>,
+}
+
+impl SeekTable {
+    /// Reads the seek table from the end of a stream, `None` if the stream has none.
+    ///
+    /// The stream position is left unspecified.
+    pub fn read<R: Read + Seek>(rd: &mut R) -> io::Result<Option<Self>> {
+        let len = rd.seek(SeekFrom::End(0))?;
+        if len < SKIPPABLE_HEADER_SIZE + FOOTER_SIZE {
+            return Ok(None);
+        }
+        let mut footer = [0u8; FOOTER_SIZE as usize];
+        rd.seek(SeekFrom::Start(len - FOOTER_SIZE))?;
+        rd.read_exact(&mut footer)?;
+        let num_frames = read_u32(&footer[0..4]) as u64;
+        let descriptor = footer[4];
+        if read_u32(&footer[5..9]) != SEEKABLE_MAGIC || descriptor & RESERVED_BITS != 0 {
+            return Ok(None);
+        }
+        let entry_size = if descriptor & CHECKSUM_FLAG != 0 {
+            12
+        } else {
+            8
+        };
+        let table_size = num_frames * entry_size + FOOTER_SIZE;
+        if len < SKIPPABLE_HEADER_SIZE + table_size {
+            return Ok(None);
+        }
+        let table_start = len - SKIPPABLE_HEADER_SIZE - table_size;
+        let mut header = [0u8; SKIPPABLE_HEADER_SIZE as usize];
+        rd.seek(SeekFrom::Start(table_start))?;
+        rd.read_exact(&mut header)?;
+        if read_u32(&header[0..4]) != SKIPPABLE_MAGIC
+            || read_u32(&header[4..8]) as u64 != table_size
+        {
+            return Ok(None);
+        }
+
+        let mut entries = vec![0u8; (num_frames * entry_size) as usize];
+        rd.read_exact(&mut entries)?;
+        let mut frames = Vec::with_capacity(num_frames as usize);
+        let mut compressed_offset = 0;
+        let mut decompressed_offset = 0;
+        for entry in entries.chunks_exact(entry_size as usize) {
+            let compressed_size = read_u32(&entry[0..4]) as u64;
+            let decompressed_size = read_u32(&entry[4..8]) as u64;
+            frames.push(Frame {
+                compressed_offset,
+                compressed_size,
+                decompressed_offset,
+                decompressed_size,
+            });
+            compressed_offset += compressed_size;
+            decompressed_offset += decompressed_size;
+        }
+        if compressed_offset != table_start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "zstd seek table does not match stream size",
+            ));
+        }
+        Ok(Some(Self { frames }))
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Size of the decompressed stream.
+    pub fn decompressed_len(&self) -> u64 {
+        self.frames.last().map(Frame::decompressed_end).unwrap_or(0)
+    }
+
+    /// Index of the frame containing the decompressed offset.
+    fn frame_index(&self, offset: u64) -> Option<usize> {
+        let index = self
+            .frames
+            .partition_point(|frame| frame.decompressed_end() <= offset);
+        (index < self.frames.len()).then_some(index)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes.try_into().unwrap())
+}
+
+type FrameDecoder<R> = zstd::stream::read::Decoder<'static, BufReader<Take<R>>>;
+
+enum State<R: Read> {
+    /// No frame is being decoded.
+    Idle(R),
+    /// Decoding the frame at this index.
+    Frame(usize, FrameDecoder<R>),
+}
+
+/// Decompresses a zstd seekable stream, implementing [`Seek`] over the decompressed bytes.
+pub struct SeekableDecoder<R: Read + Seek> {
+    table: SeekTable,
+    /// Decompressed stream position.
+    pos: u64,
+    /// `None` only if opening a frame failed.
+    state: Option<State<R>>,
+}
+
+impl<R: Read + Seek> SeekableDecoder<R> {
+    /// Opens a seekable stream, or hands back the reader if it has no seek table.
+    pub fn new(mut rd: R) -> io::Result<std::result::Result<Self, R>> {
+        let table = match SeekTable::read(&mut rd)? {
+            Some(table) => table,
+            None => return Ok(Err(rd)),
+        };
+        Ok(Ok(Self {
+            table,
+            pos: 0,
+            state: Some(State::Idle(rd)),
+        }))
+    }
+
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    fn state(&mut self) -> io::Result<&mut State<R>> {
+        self.state
+            .as_mut()
+            .ok_or_else(|| io::Error::other("zstd seekable stream is broken"))
+    }
+
+    /// Stops decoding the current frame.
+    fn close_frame(&mut self) -> io::Result<()> {
+        match self.state.take() {
+            Some(State::Frame(_, decoder)) => {
+                let rd = decoder.finish().into_inner().into_inner();
+                self.state = Some(State::Idle(rd));
+            }
+            state => self.state = state,
+        }
+        self.state().map(|_| ())
+    }
+
+    /// Starts decoding the frame at `index`, skipping ahead to the current position.
+    fn open_frame(&mut self, index: usize) -> io::Result<()> {
+        self.close_frame()?;
+        let frame = self.table.frames[index];
+        let mut rd = match self.state.take() {
+            Some(State::Idle(rd)) => rd,
+            _ => unreachable!(),
+        };
+        rd.seek(SeekFrom::Start(frame.compressed_offset))?;
+        let mut decoder =
+            zstd::stream::read::Decoder::new(rd.take(frame.compressed_size))?.single_frame();
+        skip(&mut decoder, self.pos - frame.decompressed_offset)?;
+        self.state = Some(State::Frame(index, decoder));
+        Ok(())
+    }
+}
+
+/// Discards `n` bytes from a reader.
+fn skip<R: Read>(rd: &mut R, n: u64) -> io::Result<()> {
+    if io::copy(&mut rd.take(n), &mut io::sink())? != n {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+impl<R: Read + Seek> Read for SeekableDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let index = match self.table.frame_index(self.pos) {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        if !matches!(self.state()?, State::Frame(current, _) if *current == index) {
+            self.open_frame(index)?;
+        }
+        let decoder = match self.state()? {
+            State::Frame(_, decoder) => decoder,
+            State::Idle(_) => unreachable!(),
+        };
+        let n = decoder.read(buf)?;
+        if n == 0 && !buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "zstd frame is shorter than its seek table entry",
+            ));
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.table.decompressed_len().checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+        // Short forward seeks within the current frame are cheaper to decode than reopening it.
+        let target_frame = self.table.frame_index(target);
+        let pos = self.pos;
+        match self.state()? {
+            State::Frame(current, decoder) if target >= pos && target_frame == Some(*current) => {
+                skip(decoder, target - pos)?;
+            }
+            _ => self.close_frame()?,
+        }
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+/// A frame's compressed data, handed to a worker thread.
+struct FrameJob {
+    compressed: Vec<u8>,
+    decompressed_size: usize,
+    result: SyncSender<io::Result<Vec<u8>>>,
+}
+
+/// Decompresses a zstd seekable stream on several worker threads, like [`SeekableDecoder`].
+///
+/// Frames are independent, so the workers decompress the frames following the current
+/// position ahead of the reader. Their compressed data is read on the calling thread.
+/// Seeking to a frame that isn't already being decompressed discards the frames ahead.
+pub struct ParallelSeekableDecoder<R: Read + Seek> {
+    rd: R,
+    table: SeekTable,
+    /// Decompressed stream position.
+    pos: u64,
+    /// Decompressed frame containing the position, if it was already needed.
+    current: Option<(usize, Vec<u8>)>,
+    /// Frames handed to the workers, in stream order.
+    pending: VecDeque<(usize, Receiver<io::Result<Vec<u8>>>)>,
+    /// Index of the next frame to hand to the workers.
+    next_frame: usize,
+    max_pending: usize,
+    jobs: Sender<FrameJob>,
+}
+
+impl<R: Read + Seek> ParallelSeekableDecoder<R> {
+    /// Opens a seekable stream with `num_threads` workers, or hands back the reader
+    /// if it has no seek table.
+    pub fn new(mut rd: R, num_threads: usize) -> io::Result<std::result::Result<Self, R>> {
+        let table = match SeekTable::read(&mut rd)? {
+            Some(table) => table,
+            None => return Ok(Err(rd)),
+        };
+        let num_threads = num_threads.max(1);
+        let (jobs, rx) = channel::<FrameJob>();
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..num_threads {
+            let rx = Arc::clone(&rx);
+            std::thread::spawn(move || loop {
+                // Exits once the decoder is dropped.
+                let job = match rx.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                let result = decompress_frame(&job.compressed, job.decompressed_size);
+                let _ = job.result.send(result);
+            });
+        }
+        Ok(Ok(Self {
+            rd,
+            table,
+            pos: 0,
+            current: None,
+            pending: VecDeque::new(),
+            next_frame: 0,
+            // Keeps every worker busy while the reader consumes a frame.
+            max_pending: 2 * num_threads,
+            jobs,
+        }))
+    }
+
+    pub fn seek_table(&self) -> &SeekTable {
+        &self.table
+    }
+
+    /// Returns the decompressed frame at `index`, handing the following frames to the workers.
+    fn take_frame(&mut self, index: usize) -> io::Result<Vec<u8>> {
+        while self
+            .pending
+            .front()
+            .is_some_and(|(pending, _)| *pending < index)
+        {
+            self.pending.pop_front();
+        }
+        if self.pending.front().map(|(pending, _)| *pending) != Some(index) {
+            self.pending.clear();
+            self.next_frame = index;
+        }
+        while self.pending.len() < self.max_pending && self.next_frame < self.table.frames.len() {
+            self.submit(self.next_frame)?;
+            self.next_frame += 1;
+        }
+        let (_, result) = self.pending.pop_front().unwrap();
+        result
+            .recv()
+            .map_err(|_| io::Error::other("zstd worker thread exited"))?
+    }
+
+    fn submit(&mut self, index: usize) -> io::Result<()> {
+        let frame = self.table.frames[index];
+        let mut compressed = vec![0u8; frame.compressed_size as usize];
+        self.rd.seek(SeekFrom::Start(frame.compressed_offset))?;
+        self.rd.read_exact(&mut compressed)?;
+        let (result, rx) = sync_channel(1);
+        self.jobs
+            .send(FrameJob {
+                compressed,
+                decompressed_size: frame.decompressed_size as usize,
+                result,
+            })
+            .map_err(|_| io::Error::other("zstd worker threads exited"))?;
+        self.pending.push_back((index, rx));
+        Ok(())
+    }
+}
+
+fn decompress_frame(compressed: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+    let decompressed = zstd::bulk::decompress(compressed, decompressed_size)?;
+    if decompressed.len() != decompressed_size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "zstd frame is shorter than its seek table entry",
+        ));
+    }
+    Ok(decompressed)
+}
+
+impl<R: Read + Seek> Read for ParallelSeekableDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let index = match self.table.frame_index(self.pos) {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        if !matches!(&self.current, Some((current, _)) if *current == index) {
+            self.current = None;
+            self.current = Some((index, self.take_frame(index)?));
+        }
+        let data = match &self.current {
+            Some((_, data)) => data,
+            None => unreachable!(),
+        };
+        let start = (self.pos - self.table.frames[index].decompressed_offset) as usize;
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for ParallelSeekableDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.table.decompressed_len().checked_add_signed(delta),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek position"))?;
+        // Frames are fetched on the next read, so seeks within pending frames keep them.
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Compresses each chunk into its own frame, followed by a seek table without checksums.
+    fn seekable(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut entries = Vec::new();
+        for chunk in chunks {
+            let frame = zstd::bulk::compress(chunk, 3).unwrap();
+            entries.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            entries.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            out.extend_from_slice(&frame);
+        }
+        out.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        out.extend_from_slice(&((entries.len() as u64 + FOOTER_SIZE) as u32).to_le_bytes());
+        out.extend_from_slice(&entries);
+        out.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+        out.push(0);
+        out.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        out
+    }
+
+    fn chunks() -> Vec<Vec<u8>> {
+        (0..10u8)
+            .map(|i| {
+                (0..1000 + i as usize * 100)
+                    .map(|j| (j as u8) ^ i)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parallel_decoder_reads_all_frames() {
+        let chunks = chunks();
+        let mut decoder = ParallelSeekableDecoder::new(Cursor::new(seekable(&chunks)), 3)
+            .unwrap()
+            .ok()
+            .unwrap();
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data).unwrap();
+        assert_eq!(data, chunks.concat());
+    }
+
+    #[test]
+    fn parallel_decoder_seeks() {
+        let data = chunks().concat();
+        let mut decoder = ParallelSeekableDecoder::new(Cursor::new(seekable(&chunks())), 3)
+            .unwrap()
+            .ok()
+            .unwrap();
+        // Forward within a frame, across pending frames, past them, and back.
+        for (pos, expected) in [
+            (SeekFrom::Start(10), 10),
+            (SeekFrom::Current(500), 2010),
+            (SeekFrom::Start(2500), 2500),
+            (SeekFrom::End(-700), data.len() as u64 - 700),
+            (SeekFrom::Start(1200), 1200),
+        ] {
+            assert_eq!(decoder.seek(pos).unwrap(), expected);
+            let start = expected as usize;
+            let end = data.len().min(start + 1500);
+            let mut actual = vec![0u8; end - start];
+            decoder.read_exact(&mut actual).unwrap();
+            assert_eq!(actual, data[start..end]);
+        }
+    }
+
+    #[test]
+    fn parallel_decoder_rejects_plain_zstd() {
+        let plain = zstd::bulk::compress(&[1u8; 100], 3).unwrap();
+        assert!(ParallelSeekableDecoder::new(Cursor::new(plain), 2)
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,69 @@
+//! Reads the status cache, which records the results of recent transactions.
+
+use crate::solana::deserialize_from;
+use crate::Result;
+use solana_sdk::clock::Slot;
+use solana_sdk::hash::Hash;
+use solana_sdk::transaction::TransactionError;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path};
+
+/// Number of bytes of each transaction key kept in the status cache.
+pub const CACHED_KEY_SIZE: usize = 20;
+
+type TransactionStatus = std::result::Result<(), TransactionError>;
+
+/// Layout of `solana_runtime::bank::BankSlotDelta`.
+///
+/// The runtime wraps the map in `Arc<Mutex<_>>`, which serializes as the inner value.
+type SlotDelta = (
+    Slot,
+    bool,
+    HashMap<Hash, (usize, Vec<([u8; CACHED_KEY_SIZE], TransactionStatus)>)>,
+);
+
+/// A transaction status recorded in the status cache.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusCacheEntry {
+    pub slot: Slot,
+    /// Whether the slot was rooted when the snapshot was taken.
+    pub is_root: bool,
+    /// Recent blockhash of the transaction.
+    pub blockhash: Hash,
+    /// Offset of `key_slice` in the key, chosen randomly per blockhash.
+    pub key_index: usize,
+    /// Bytes `key_index..key_index + 20` of the transaction signature or message hash,
+    /// both of which are recorded for every transaction.
+    pub key_slice: [u8; CACHED_KEY_SIZE],
+    pub status: TransactionStatus,
+}
+
+/// Reads a `snapshots/status_cache` file.
+pub fn read_status_cache<R: Read>(rd: R) -> Result<Vec<StatusCacheEntry>> {
+    let slot_deltas: Vec<SlotDelta> = deserialize_from(rd)?;
+    let mut entries = Vec::new();
+    for (slot, is_root, statuses) in slot_deltas {
+        for (blockhash, (key_index, keys)) in statuses {
+            entries.extend(
+                keys.into_iter()
+                    .map(|(key_slice, status)| StatusCacheEntry {
+                        slot,
+                        is_root,
+                        blockhash,
+                        key_index,
+                        key_slice,
+                        status,
+                    }),
+            );
+        }
+    }
+    Ok(entries)
+}
+
+pub(crate) fn is_status_cache_file(path: &Path) -> bool {
+    let mut components = path.components();
+    components.next() == Some(Component::Normal("snapshots".as_ref()))
+        && components.next() == Some(Component::Normal("status_cache".as_ref()))
+        && components.next().is_none()
+}
@@ -0,0 +1,141 @@
+//! Sysvar-like bank parameters from the snapshot manifest.
+//!
+//! These are copies of the Solana types with only public fields, so that they don't change
+//! with the Solana version this crate is built against.
+
+use crate::solana::DeserializableVersionedBank;
+use serde::{Deserialize, Serialize};
+use solana_sdk::clock::{Epoch, Slot};
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::rent::Rent;
+
+/// Epoch schedule, rent, and fee parameters of the bank.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotSysvars {
+    pub epoch_schedule: EpochScheduleParams,
+    pub rent: RentParams,
+    pub fees: FeeParams,
+}
+
+impl From<&DeserializableVersionedBank> for SnapshotSysvars {
+    fn from(bank: &DeserializableVersionedBank) -> Self {
+        let schedule = &bank.epoch_schedule;
+        let rent = &bank.rent_collector.rent;
+        let governor = &bank.fee_rate_governor;
+        Self {
+            epoch_schedule: EpochScheduleParams {
+                slots_per_epoch: schedule.slots_per_epoch,
+                leader_schedule_slot_offset: schedule.leader_schedule_slot_offset,
+                warmup: schedule.warmup,
+                first_normal_epoch: schedule.first_normal_epoch,
+                first_normal_slot: schedule.first_normal_slot,
+            },
+            rent: RentParams {
+                lamports_per_byte_year: rent.lamports_per_byte_year,
+                exemption_threshold: rent.exemption_threshold,
+                burn_percent: rent.burn_percent,
+            },
+            fees: FeeParams {
+                // Not serialized in the fee rate governor, the fee calculator holds it.
+                lamports_per_signature: bank.fee_calculator.lamports_per_signature,
+                target_lamports_per_signature: governor.target_lamports_per_signature,
+                target_signatures_per_slot: governor.target_signatures_per_slot,
+                min_lamports_per_signature: governor.min_lamports_per_signature,
+                max_lamports_per_signature: governor.max_lamports_per_signature,
+                burn_percent: governor.burn_percent,
+            },
+        }
+    }
+}
+
+/// Slots per epoch, including the shorter warmup epochs at the start of a cluster.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpochScheduleParams {
+    pub slots_per_epoch: u64,
+    /// Number of slots before an epoch starts to calculate its leader schedule.
+    pub leader_schedule_slot_offset: u64,
+    /// Whether epochs start short and grow.
+    pub warmup: bool,
+    /// First epoch with `slots_per_epoch` slots.
+    pub first_normal_epoch: Epoch,
+    pub first_normal_slot: Slot,
+}
+
+impl EpochScheduleParams {
+    /// Returns the epoch containing `slot`.
+    ///
+    /// ```
+    /// use solana_snapshot_etl::sysvars::EpochScheduleParams;
+    ///
+    /// let schedule = EpochScheduleParams {
+    ///     slots_per_epoch: 432_000,
+    ///     leader_schedule_slot_offset: 432_000,
+    ///     warmup: false,
+    ///     first_normal_epoch: 0,
+    ///     first_normal_slot: 0,
+    /// };
+    /// assert_eq!(schedule.epoch(139_240_745), 322);
+    /// assert_eq!(schedule.first_slot_in_epoch(322), 139_104_000);
+    /// ```
+    pub fn epoch(&self, slot: Slot) -> Epoch {
+        self.to_solana().get_epoch(slot)
+    }
+
+    pub fn first_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+        self.to_solana().get_first_slot_in_epoch(epoch)
+    }
+
+    pub fn last_slot_in_epoch(&self, epoch: Epoch) -> Slot {
+        self.to_solana().get_last_slot_in_epoch(epoch)
+    }
+
+    fn to_solana(self) -> EpochSchedule {
+        EpochSchedule {
+            slots_per_epoch: self.slots_per_epoch,
+            leader_schedule_slot_offset: self.leader_schedule_slot_offset,
+            warmup: self.warmup,
+            first_normal_epoch: self.first_normal_epoch,
+            first_normal_slot: self.first_normal_slot,
+        }
+    }
+}
+
+/// Rent parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RentParams {
+    pub lamports_per_byte_year: u64,
+    /// Years of rent an account must hold to be exempt.
+    pub exemption_threshold: f64,
+    /// Percentage of collected rent that is burned.
+    pub burn_percent: u8,
+}
+
+impl RentParams {
+    /// Returns the minimum balance for an account with `data_len` bytes of data to be rent-exempt.
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        self.to_solana().minimum_balance(data_len)
+    }
+
+    fn to_solana(self) -> Rent {
+        Rent {
+            lamports_per_byte_year: self.lamports_per_byte_year,
+            exemption_threshold: self.exemption_threshold,
+            burn_percent: self.burn_percent,
+        }
+    }
+}
+
+/// Transaction fee parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeeParams {
+    /// Fee per signature of the bank.
+    pub lamports_per_signature: u64,
+    /// Fee per signature when the cluster processes `target_signatures_per_slot`.
+    pub target_lamports_per_signature: u64,
+    /// Zero if the fee is not adjusted to the load.
+    pub target_signatures_per_slot: u64,
+    pub min_lamports_per_signature: u64,
+    pub max_lamports_per_signature: u64,
+    /// Percentage of collected fees that is burned.
+    pub burn_percent: u8,
+}
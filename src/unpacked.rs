@@ -1,13 +1,20 @@
+use crate::append_vec::DEFAULT_MAX_DATA_LEN;
+use crate::compression::Compression;
+use crate::status_cache::{read_status_cache, StatusCacheEntry};
+use crate::sysvars::SnapshotSysvars;
 use crate::{
-    deserialize_from, parse_append_vec_name, AccountsDbFields, AppendVec, AppendVecIterator,
-    DeserializableVersionedBank, ReadProgressTracking, Result, SerializableAccountStorageEntry,
-    SnapshotError, SnapshotExtractor, SNAPSHOTS_DIR,
+    checked_append_vec_len, deserialize_from, parse_accounts_file_name, AccountsDbFields,
+    AppendVec, AppendVecIterator, BankSummary, DeserializableVersionedBank, ReadProgressTracking,
+    Result, SerializableAccountStorageEntry, SnapshotError, SnapshotExtractor, SNAPSHOTS_DIR,
 };
 use itertools::Itertools;
 use log::info;
 use solana_runtime::snapshot_utils::SNAPSHOT_STATUS_CACHE_FILENAME;
-use std::fs::OpenOptions;
+use solana_sdk::clock::Slot;
+use std::ffi::OsStr;
+use std::fs::{OpenOptions, ReadDir};
 use std::io::BufReader;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time::Instant;
@@ -15,7 +22,12 @@ use std::time::Instant;
 /// Extracts account data from snapshots that were unarchived to a file system.
 pub struct UnpackedSnapshotExtractor {
     root: PathBuf,
+    status_cache_path: PathBuf,
     accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry>,
+    bank: BankSummary,
+    slot_range: RangeInclusive<Slot>,
+    strict: bool,
+    max_data_len: u64,
 }
 
 impl SnapshotExtractor for UnpackedSnapshotExtractor {
@@ -37,27 +49,37 @@ impl UnpackedSnapshotExtractor {
         let snapshot_file_path = snapshot_files
             .filter_map(|entry| entry.ok())
             .find(|entry| u64::from_str(&entry.file_name().to_string_lossy()).is_ok())
-            .map(|entry| entry.path().join(entry.file_name()))
+            .and_then(|entry| Self::find_manifest_file(&entry.path(), &entry.file_name()))
             .ok_or(SnapshotError::NoSnapshotManifest)?;
 
         info!("Opening snapshot manifest: {:?}", snapshot_file_path);
         let snapshot_file = OpenOptions::new().read(true).open(&snapshot_file_path)?;
-        let snapshot_file_len = snapshot_file.metadata()?.len();
+        let mut snapshot_file_len = snapshot_file.metadata()?.len();
+
+        let mut snapshot_file = BufReader::new(snapshot_file);
+        let compression = Compression::detect(&mut snapshot_file, &snapshot_file_path)?;
+        if compression != Compression::None {
+            info!("Snapshot manifest is compressed with {:?}", compression);
+            // Decompressed size is not known upfront.
+            snapshot_file_len = 0;
+        }
 
         let snapshot_file = progress_tracking.new_read_progress_tracker(
             &snapshot_file_path,
-            Box::new(snapshot_file),
+            compression.decoder(snapshot_file)?,
             snapshot_file_len,
         );
         let mut snapshot_file = BufReader::new(snapshot_file);
 
         let pre_unpack = Instant::now();
         let versioned_bank: DeserializableVersionedBank = deserialize_from(&mut snapshot_file)?;
+        let mut bank = BankSummary::from(&versioned_bank);
         drop(versioned_bank);
         let versioned_bank_post_time = Instant::now();
 
         let accounts_db_fields: AccountsDbFields<SerializableAccountStorageEntry> =
             deserialize_from(&mut snapshot_file)?;
+        bank.set_accounts_db_fields(&accounts_db_fields);
         let accounts_db_fields_post_time = Instant::now();
         drop(snapshot_file);
 
@@ -72,30 +94,87 @@ impl UnpackedSnapshotExtractor {
 
         Ok(UnpackedSnapshotExtractor {
             root: path.to_path_buf(),
+            status_cache_path: status_cache,
             accounts_db_fields,
+            bank,
+            slot_range: 0..=Slot::MAX,
+            strict: false,
+            max_data_len: DEFAULT_MAX_DATA_LEN,
         })
     }
 
+    /// Bank fields from the snapshot manifest.
+    pub fn bank(&self) -> &BankSummary {
+        &self.bank
+    }
+
+    /// Epoch schedule, rent, and fee parameters from the snapshot manifest.
+    pub fn sysvars(&self) -> &SnapshotSysvars {
+        &self.bank.sysvars
+    }
+
+    /// Reads the status cache.
+    pub fn status_cache(&self) -> Result<Vec<StatusCacheEntry>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .open(&self.status_cache_path)?;
+        read_status_cache(BufReader::new(file))
+    }
+
+    /// Finds the manifest in a slot directory, which may carry a compression extension.
+    fn find_manifest_file(slot_dir: &Path, slot: &OsStr) -> Option<PathBuf> {
+        ["", ".gz", ".xz", ".zst"]
+            .iter()
+            .map(|ext| {
+                let mut name = slot.to_os_string();
+                name.push(ext);
+                slot_dir.join(name)
+            })
+            .find(|path| path.is_file())
+    }
+
+    /// Only yields AppendVecs whose slot is within the range.
+    pub fn set_slot_range(&mut self, slot_range: RangeInclusive<Slot>) {
+        self.slot_range = slot_range;
+    }
+
+    /// Fails on AppendVecs that are shorter than declared in the manifest, instead of warning.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets the largest `data_len` accepted from accounts, see [`AppendVec::set_max_data_len`].
+    pub fn set_max_data_len(&mut self, max_data_len: u64) {
+        self.max_data_len = max_data_len;
+    }
+
     pub fn unboxed_iter(&self) -> impl Iterator<Item = Result<AppendVec>> + '_ {
+        // A second `flatten_ok` would drop errors of individual AppendVecs,
+        // since an `Err` flattens into an empty iterator.
         std::iter::once(self.iter_streams())
             .flatten_ok()
-            .flatten_ok()
+            .map(|append_vec| append_vec.and_then(|append_vec| append_vec))
     }
 
     fn iter_streams(&self) -> Result<impl Iterator<Item = Result<AppendVec>> + '_> {
-        let accounts_dir = self.root.join("accounts");
+        let accounts_dir = self.accounts_dir();
         Ok(accounts_dir
             .read_dir()?
             .filter_map(|f| f.ok())
             .filter_map(|f| {
                 let name = f.file_name();
-                parse_append_vec_name(&f.file_name()).map(move |parsed| (parsed, name))
+                parse_accounts_file_name(&f.file_name()).map(move |parsed| (parsed, name))
             })
+            .filter(move |((slot, _), _)| self.slot_range.contains(slot))
             .map(move |((slot, version), name)| {
                 self.open_append_vec(slot, version, &accounts_dir.join(name))
             }))
     }
 
+    fn accounts_dir(&self) -> PathBuf {
+        self.root.join("accounts")
+    }
+
     fn open_append_vec(&self, slot: u64, id: u64, path: &Path) -> Result<AppendVec> {
         let known_vecs = self
             .accounts_db_fields
@@ -109,9 +188,60 @@ impl UnpackedSnapshotExtractor {
             Some(v) => v,
         };
 
-        Ok(AppendVec::new_from_file(
-            path,
+        let len = checked_append_vec_len(
+            slot,
+            id,
+            std::fs::metadata(path)?.len(),
             known_vec.accounts_current_len,
-        )?)
+            self.strict,
+        )?;
+        let mut append_vec = AppendVec::new_from_file(path, len, slot, id)?;
+        append_vec.set_max_data_len(self.max_data_len);
+        Ok(append_vec)
+    }
+}
+
+impl IntoIterator for UnpackedSnapshotExtractor {
+    type Item = Result<AppendVec>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let read_dir = self.accounts_dir().read_dir();
+        IntoIter {
+            extractor: self,
+            read_dir: Some(read_dir),
+        }
+    }
+}
+
+/// Owning iterator over the AppendVecs of an unpacked snapshot,
+/// see [`UnpackedSnapshotExtractor::into_iter`].
+pub struct IntoIter {
+    extractor: UnpackedSnapshotExtractor,
+    /// Listing of the accounts directory, `None` after a listing error was returned.
+    read_dir: Option<std::io::Result<ReadDir>>,
+}
+
+impl Iterator for IntoIter {
+    type Item = Result<AppendVec>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let file = match self.read_dir.as_mut()? {
+                Ok(read_dir) => read_dir.next()?,
+                Err(_) => return self.read_dir.take()?.err().map(|e| Err(e.into())),
+            };
+            let file = match file {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+            let (slot, id) = match parse_accounts_file_name(&file.file_name()) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+            if self.extractor.slot_range.contains(&slot) {
+                return Some(self.extractor.open_append_vec(slot, id, &file.path()));
+            }
+        }
     }
 }
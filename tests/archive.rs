@@ -0,0 +1,104 @@
+mod common;
+
+use common::{accounts, write_unpacked_snapshot, BANK_SLOT};
+use solana_snapshot_etl::archived::ArchiveSnapshotExtractor;
+use solana_snapshot_etl::{for_each_account, OwnedAccount};
+#[cfg(any(feature = "bzip2", feature = "gzip"))]
+use std::io::Write;
+use std::path::Path;
+
+/// Packs an unpacked snapshot into a tar, manifest first like Solana does.
+fn tar_snapshot(dir: &Path) -> Vec<u8> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let manifest = format!("snapshots/{}/{}", BANK_SLOT, BANK_SLOT);
+    for name in ["snapshots/status_cache", &manifest] {
+        builder.append_path_with_name(dir.join(name), name).unwrap();
+    }
+    let mut append_vecs: Vec<_> = std::fs::read_dir(dir.join("accounts"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    append_vecs.sort();
+    for name in append_vecs {
+        let path = Path::new("accounts").join(name);
+        builder
+            .append_path_with_name(dir.join(&path), path)
+            .unwrap();
+    }
+    builder.into_inner().unwrap()
+}
+
+/// Writes a snapshot archive compressed by `compress` and returns its accounts.
+fn write_archive(path: &Path, compress: impl FnOnce(&[u8]) -> Vec<u8>) -> Vec<OwnedAccount> {
+    let snapshot_dir = path.with_extension("dir");
+    let accounts = accounts(10);
+    write_unpacked_snapshot(&snapshot_dir, std::slice::from_ref(&accounts));
+    std::fs::write(path, compress(&tar_snapshot(&snapshot_dir))).unwrap();
+    accounts
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(tar: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(tar).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn read_archive(path: &Path) -> Vec<OwnedAccount> {
+    let mut extractor = ArchiveSnapshotExtractor::open(path).unwrap();
+    let mut accounts = Vec::new();
+    for_each_account(&mut extractor, |account| {
+        accounts.push(OwnedAccount::from(account));
+        Ok(())
+    })
+    .unwrap();
+    accounts
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn opens_tar_gz() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot.tar.gz");
+    let accounts = write_archive(&path, gzip);
+    assert_eq!(read_archive(&path), accounts);
+}
+
+#[test]
+#[cfg(feature = "bzip2")]
+fn opens_tar_bz2() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot.tar.bz2");
+    let accounts = write_archive(&path, |tar| {
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(tar).unwrap();
+        encoder.finish().unwrap()
+    });
+    assert_eq!(read_archive(&path), accounts);
+}
+
+#[test]
+fn opens_tar_zst() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot.tar.zst");
+    let accounts = write_archive(&path, |tar| zstd::encode_all(tar, 0).unwrap());
+    assert_eq!(read_archive(&path), accounts);
+}
+
+#[test]
+#[cfg(feature = "gzip")]
+fn detects_codec_by_magic_despite_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot.bin");
+    let accounts = write_archive(&path, gzip);
+    assert_eq!(read_archive(&path), accounts);
+}
+
+#[test]
+fn unknown_extension_without_magic_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("snapshot.tar.lz4");
+    // Neither a known codec nor a tar.
+    std::fs::write(&path, [0x04, 0x22, 0x4d, 0x18].repeat(256)).unwrap();
+    assert!(ArchiveSnapshotExtractor::open(&path).is_err());
+}
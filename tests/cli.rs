@@ -0,0 +1,57 @@
+mod common;
+
+use common::{accounts, write_unpacked_snapshot};
+use std::path::Path;
+use std::process::Command;
+
+/// Runs the CLI on a snapshot and returns its exit code.
+fn exit_code(snapshot: &Path, args: &[&str]) -> i32 {
+    Command::new(env!("CARGO_BIN_EXE_solana-snapshot-etl"))
+        .arg("--quiet")
+        .args(args)
+        .arg(snapshot)
+        .env("RUST_LOG", "off")
+        .status()
+        .unwrap()
+        .code()
+        .unwrap()
+}
+
+/// Writes a snapshot whose first AppendVec ends in the middle of an account.
+fn truncated_snapshot(dir: &Path) {
+    let paths = write_unpacked_snapshot(dir, &[accounts(10), accounts(10)]);
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&paths[0])
+        .unwrap();
+    file.set_len(file.metadata().unwrap().len() - 20).unwrap();
+}
+
+#[test]
+fn check_passes_on_intact_snapshot() {
+    let dir = tempfile::tempdir().unwrap();
+    write_unpacked_snapshot(dir.path(), &[accounts(10), accounts(10)]);
+    assert_eq!(exit_code(dir.path(), &["--check"]), 0);
+    assert_eq!(exit_code(dir.path(), &["--verify-capitalization"]), 0);
+}
+
+#[test]
+fn check_fails_with_parse_exit_code_on_truncated_append_vec() {
+    let dir = tempfile::tempdir().unwrap();
+    truncated_snapshot(dir.path());
+    assert_eq!(exit_code(dir.path(), &["--check"]), 4);
+}
+
+#[test]
+fn capitalization_mismatch_fails_with_parse_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    // The last account of the truncated AppendVec is missing from the sum.
+    truncated_snapshot(dir.path());
+    assert_eq!(exit_code(dir.path(), &["--verify-capitalization"]), 4);
+}
+
+#[test]
+fn missing_snapshot_fails_with_load_exit_code() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(exit_code(&dir.path().join("missing"), &["--check"]), 3);
+}
@@ -0,0 +1,170 @@
+//! Writes small unpacked snapshots for the integration tests.
+
+use serde::Serialize;
+use solana_runtime::accounts_db::BankHashInfo;
+use solana_runtime::blockhash_queue::BlockhashQueue;
+use solana_runtime::epoch_stakes::EpochStakes;
+use solana_runtime::rent_collector::RentCollector;
+use solana_runtime::stakes::Stakes;
+use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::fee_calculator::{FeeCalculator, FeeRateGovernor};
+use solana_sdk::hard_forks::HardForks;
+use solana_sdk::hash::Hash;
+use solana_sdk::inflation::Inflation;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::state::Delegation;
+use solana_snapshot_etl::append_vec::AppendVec;
+use solana_snapshot_etl::OwnedAccount;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Slot of the bank in snapshots written by [`write_unpacked_snapshot`].
+pub const BANK_SLOT: u64 = 100;
+
+#[derive(Serialize, Default)]
+struct UnusedAccounts {
+    unused1: HashSet<Pubkey>,
+    unused2: HashSet<Pubkey>,
+    unused3: HashMap<Pubkey, u64>,
+}
+
+/// Serialized like `DeserializableVersionedBank`.
+#[derive(Serialize)]
+struct Bank {
+    blockhash_queue: BlockhashQueue,
+    ancestors: HashMap<u64, usize>,
+    hash: Hash,
+    parent_hash: Hash,
+    parent_slot: u64,
+    hard_forks: HardForks,
+    transaction_count: u64,
+    tick_height: u64,
+    signature_count: u64,
+    capitalization: u64,
+    max_tick_height: u64,
+    hashes_per_tick: Option<u64>,
+    ticks_per_slot: u64,
+    ns_per_slot: u128,
+    genesis_creation_time: i64,
+    slots_per_year: f64,
+    accounts_data_len: u64,
+    slot: u64,
+    epoch: u64,
+    block_height: u64,
+    collector_id: Pubkey,
+    collector_fees: u64,
+    fee_calculator: FeeCalculator,
+    fee_rate_governor: FeeRateGovernor,
+    collected_rent: u64,
+    rent_collector: RentCollector,
+    epoch_schedule: EpochSchedule,
+    inflation: Inflation,
+    stakes: Stakes<Delegation>,
+    unused_accounts: UnusedAccounts,
+    epoch_stakes: HashMap<u64, EpochStakes>,
+    is_delta: bool,
+}
+
+#[derive(Serialize)]
+struct StorageEntry {
+    id: usize,
+    accounts_current_len: usize,
+}
+
+/// Returns `n` accounts with distinct pubkeys and some data.
+pub fn accounts(n: u8) -> Vec<OwnedAccount> {
+    (0..n)
+        .map(|i| OwnedAccount {
+            pubkey: Pubkey::new_unique(),
+            owner: solana_sdk::system_program::id(),
+            lamports: 1_000_000 + i as u64,
+            data: vec![i; i as usize * 3],
+            executable: false,
+            rent_epoch: 0,
+            write_version: i as u64,
+        })
+        .collect()
+}
+
+/// Writes an unpacked snapshot with one AppendVec per element of `append_vecs`,
+/// the n-th one at slot `10 + n` with ID `n`, and returns the path of each AppendVec.
+///
+/// The bank capitalization is the sum of all lamports.
+pub fn write_unpacked_snapshot(dir: &Path, append_vecs: &[Vec<OwnedAccount>]) -> Vec<PathBuf> {
+    std::fs::create_dir_all(dir.join("accounts")).unwrap();
+    let snapshot_dir = dir.join("snapshots").join(BANK_SLOT.to_string());
+    std::fs::create_dir_all(&snapshot_dir).unwrap();
+    std::fs::write(
+        dir.join("snapshots/status_cache"),
+        bincode::serialize(&Vec::<u8>::new()).unwrap(),
+    )
+    .unwrap();
+
+    let mut storages: HashMap<u64, Vec<StorageEntry>> = HashMap::new();
+    let mut paths = Vec::new();
+    let mut capitalization = 0;
+    for (id, accounts) in append_vecs.iter().enumerate() {
+        let slot = 10 + id as u64;
+        let append_vec = AppendVec::new_from_accounts(accounts, slot, id as u64).unwrap();
+        let path = dir.join("accounts").join(format!("{}.{}", slot, id));
+        std::fs::write(&path, append_vec.as_bytes()).unwrap();
+        paths.push(path);
+        storages.entry(slot).or_default().push(StorageEntry {
+            id,
+            accounts_current_len: append_vec.len(),
+        });
+        capitalization += accounts.iter().map(|a| a.lamports).sum::<u64>();
+    }
+
+    let mut blockhash_queue = BlockhashQueue::default();
+    blockhash_queue.register_hash(&Hash::new_from_array([3; 32]), 5000);
+    let bank = Bank {
+        blockhash_queue,
+        ancestors: HashMap::new(),
+        hash: Hash::new_from_array([7; 32]),
+        parent_hash: Hash::default(),
+        parent_slot: BANK_SLOT - 1,
+        hard_forks: HardForks::default(),
+        transaction_count: 0,
+        tick_height: 0,
+        signature_count: 0,
+        capitalization,
+        max_tick_height: 0,
+        hashes_per_tick: None,
+        ticks_per_slot: 64,
+        ns_per_slot: 400_000_000,
+        genesis_creation_time: 0,
+        slots_per_year: 0.0,
+        accounts_data_len: 0,
+        slot: BANK_SLOT,
+        epoch: 0,
+        block_height: 0,
+        collector_id: Pubkey::default(),
+        collector_fees: 0,
+        fee_calculator: FeeCalculator::default(),
+        fee_rate_governor: FeeRateGovernor::default(),
+        collected_rent: 0,
+        rent_collector: RentCollector::default(),
+        epoch_schedule: EpochSchedule::default(),
+        inflation: Inflation::default(),
+        stakes: Stakes::default(),
+        unused_accounts: UnusedAccounts::default(),
+        epoch_stakes: HashMap::new(),
+        is_delta: false,
+    };
+    let write_version = append_vecs.iter().map(Vec::len).sum::<usize>() as u64;
+    let mut manifest = bincode::serialize(&bank).unwrap();
+    manifest.extend(
+        bincode::serialize(&(
+            storages,
+            write_version,
+            BANK_SLOT,
+            BankHashInfo::default(),
+            Vec::<u64>::new(),
+            Vec::<(u64, Hash)>::new(),
+        ))
+        .unwrap(),
+    );
+    std::fs::write(snapshot_dir.join(BANK_SLOT.to_string()), manifest).unwrap();
+    paths
+}